@@ -0,0 +1,33 @@
+package com.archeros.wasmer;
+
+import java.util.Arrays;
+import java.util.Collections;
+import java.util.List;
+
+/**
+ * host function exchanging bytes with webAssembly memory,
+ * imported by guest as (ptr: i32, len: i32, outPtr: i32, outCap: i32) -> i32
+ * the input is read from [ptr, ptr + len), the returned bytes are written at outPtr up to outCap,
+ * and the full length of returned bytes is returned to guest
+ */
+public interface BytesHostFunction extends HostFunction {
+    /**
+     * called by webAssembly vm with the input bytes
+     */
+    byte[] execute(Instance ins, byte[] input);
+
+    @Override
+    default long[] execute(Instance ins, long[] args) {
+        throw new UnsupportedOperationException("bytes host function");
+    }
+
+    @Override
+    default List<ValType> getParams() {
+        return Arrays.asList(ValType.I32, ValType.I32, ValType.I32, ValType.I32);
+    }
+
+    @Override
+    default List<ValType> getRet() {
+        return Collections.singletonList(ValType.I32);
+    }
+}
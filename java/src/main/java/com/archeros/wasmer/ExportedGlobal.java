@@ -0,0 +1,16 @@
+package com.archeros.wasmer;
+
+/**
+ * global exported by module
+ */
+public class ExportedGlobal {
+    public final String name;
+    public final ValType type;
+    public final boolean mutable;
+
+    ExportedGlobal(String name, ValType type, boolean mutable) {
+        this.name = name;
+        this.type = type;
+        this.mutable = mutable;
+    }
+}
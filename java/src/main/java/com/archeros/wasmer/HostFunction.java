@@ -6,12 +6,14 @@ public interface HostFunction {
     long[] EMPTY_LONGS = new long[0];
 
     /**
-     * the name of host function
+     * the name of host function, "module.field" imports it from module, e.g. "math.sin",
+     * a name without module prefix is imported from env
      */
     String getName();
 
     /**
-     * called by webAssembly vm
+     * called by webAssembly vm, args is a new array on every call
+     * calling ins.execute here is rejected, memory of ins is accessible
      */
     long[] execute(Instance ins, long[] args);
 
@@ -0,0 +1,22 @@
+package com.archeros.wasmer;
+
+/**
+ * global imported by module, name is "module.field" like host functions, names without a module prefix
+ * are imported from env, mutability follows the import declaration of module
+ */
+public class HostGlobal {
+    final String name;
+    final ValType type;
+    final long value;
+
+    /**
+     * value is encoded as the arguments of execute, v128 is not supported
+     */
+    public HostGlobal(String name, ValType type, long value) {
+        if (type == ValType.V128)
+            throw new RuntimeException("v128 global is not supported");
+        this.name = name;
+        this.type = type;
+        this.value = value;
+    }
+}
@@ -1,10 +1,13 @@
 package com.archeros.wasmer;
 
+import java.util.ArrayList;
 import java.util.Collection;
+import java.util.List;
 
 
 /**
  * Instance is not thread safe, dont share Instance object between threads
+ * unless it is created with Options.sync(true)
  */
 public interface Instance extends AutoCloseable {
     long[] EMPTY_LONGS = new long[0];
@@ -13,11 +16,29 @@ public interface Instance extends AutoCloseable {
      * create new instance by webassembly byte code, and open options
      */
     static Instance create(byte[] bin, Options options, Collection<HostFunction> hosts) {
+        return create(bin, options, hosts, null);
+    }
+
+    /**
+     * create new instance with globals imported by module
+     */
+    static Instance create(byte[] bin, Options options, Collection<HostFunction> hosts, Collection<HostGlobal> globals) {
         String[] names = hosts == null ? new String[0] : hosts.stream().map(HostFunction::getName).toArray(String[]::new);
         HostFunction[] hostsArray = hosts == null ? new HostFunction[0] : hosts.toArray(new HostFunction[0]);
         byte[][] sigs = hosts == null ? new byte[0][] :
-                hosts.stream().map(x -> Natives.encodeSignature(x.getParams(), x.getRet()))
+                hosts.stream().map(x -> x instanceof BytesHostFunction ? Natives.BYTES_SIGNATURE : Natives.encodeSignature(x.getParams(), x.getRet()))
                         .toArray(byte[][]::new);
+        String[] globalNames = globals == null ? new String[0] : globals.stream().map(x -> x.name).toArray(String[]::new);
+        byte[] globalTypes = new byte[globalNames.length];
+        long[] globalValues = new long[globalNames.length];
+        if (globals != null) {
+            int i = 0;
+            for (HostGlobal g : globals) {
+                globalTypes[i] = g.type.value();
+                globalValues[i] = g.value;
+                i++;
+            }
+        }
 
         InstanceImpl ins = new InstanceImpl();
         int insId = -1;
@@ -42,12 +63,64 @@ public interface Instance extends AutoCloseable {
         ins.id = insId;
         Natives.HOST_FUNCTIONS[insId] = hostsArray;
 
-        long descriptor = Natives.createInstance(bin, options.bitmap(), insId, names, sigs);
-        ins.descriptor = descriptor;
-        ins.mem = new MemoryImpl(descriptor);
+        boolean created = false;
+        try {
+            ins.descriptor = Natives.createInstance(bin, options.bitmap(), insId, names, sigs, globalNames, globalTypes, globalValues);
+            created = true;
+        } finally {
+            // give the slot back when the module fails to compile or instantiate
+            if (!created) {
+                Natives.MUTEX.lock();
+                try {
+                    Natives.INSTANCES[insId] = null;
+                    Natives.HOST_FUNCTIONS[insId] = null;
+                } finally {
+                    Natives.MUTEX.unlock();
+                }
+            }
+        }
+        ins.mem = new MemoryImpl(ins);
         return ins;
     }
 
+    /**
+     * function imports the module requires from host functions, with their signatures
+     */
+    static List<RequiredImport> requiredImports(byte[] bin, Options options) {
+        Object[] r = Natives.requiredImports(bin, options.bitmap());
+        String[] names = (String[]) r[0];
+        byte[][] params = (byte[][]) r[1];
+        byte[][] ret = (byte[][]) r[2];
+        List<RequiredImport> imports = new ArrayList<>(names.length);
+        for (int i = 0; i < names.length; i++) {
+            List<ValType> p = new ArrayList<>(params[i].length);
+            for (byte t : params[i]) {
+                p.add(ValType.of(t));
+            }
+            List<ValType> rets = new ArrayList<>(ret[i].length);
+            for (byte t : ret[i]) {
+                rets.add(ValType.of(t));
+            }
+            imports.add(new RequiredImport(names[i], p, rets));
+        }
+        return imports;
+    }
+
+    /**
+     * check the webassembly byte code compiles under options, without instantiation
+     */
+    static void validate(byte[] bin, Options options) {
+        Natives.validateModule(bin, options.bitmap());
+    }
+
+    /**
+     * read custom section of the webassembly byte code without compiling it, e.g. "name" for debugging,
+     * return null if there is no such section
+     */
+    static byte[] customSection(byte[] bin, String name) {
+        return Natives.customSection(bin, name);
+    }
+
     Memory getMemory(String name);
 
     /**
@@ -55,6 +128,39 @@ public interface Instance extends AutoCloseable {
      */
     long[] execute(String export, long[] args);
 
+    /**
+     * execute exported function, results are returned with their value types
+     */
+    TypedResults executeWithTypes(String export, long[] args);
+
+    /**
+     * execute exported function once per argument array with a single native call,
+     * the batch fails as a whole if any argument array doesn't match the signature
+     */
+    long[][] executeBatch(String export, long[][] args);
+
+    /**
+     * execute function stored at index of the exported table, for dynamic dispatch from host,
+     * the function has to be exported by module as well, host functions in the table are not supported
+     */
+    long[] executeTable(String table, int index, long[] args);
+
+    /**
+     * execute exported function, and read the result written to memory at [off, off + len)
+     */
+    byte[] executeAndPeek(String export, long[] args, int off, int len);
+
+    /**
+     * restore exported memories and mutable globals to their values after instantiation,
+     * grown memory is zeroed but not shrunk, requires the instance created with Options.reset(true)
+     */
+    void reset();
+
+    /**
+     * names, types and mutability of globals exported by module, funcref and externref globals are left out
+     */
+    List<ExportedGlobal> exportedGlobals();
+
     @Override
     void close();
 }
@@ -1,25 +1,51 @@
 package com.archeros.wasmer;
 
+import java.util.ArrayList;
+import java.util.List;
 
 class MemoryImpl implements Memory {
-    long descriptor;
+    static final String DEFAULT_NAME = "memory";
+
+    // descriptor is read through the instance, so memories obtained before close don't keep a stale one
+    final InstanceImpl instance;
+    final String name;
+
+    public MemoryImpl(InstanceImpl instance) {
+        this(instance, DEFAULT_NAME);
+    }
 
-    public MemoryImpl(long desc) {
-        this.descriptor = desc;
+    public MemoryImpl(InstanceImpl instance, String name) {
+        this.instance = instance;
+        this.name = name;
     }
 
     public byte[] read(int off, int len) {
         if (off < 0 || len < 0) {
             throw new RuntimeException("off or len shouldn't be negative");
         }
-        return Natives.getMemory(this.descriptor, off, len);
+        if (DEFAULT_NAME.equals(name)) {
+            return Natives.getMemory(instance.descriptor, off, len);
+        }
+        return Natives.getMemoryNamed(instance.descriptor, name, off, len);
     }
 
     public void write(int off, byte[] buf) {
         if (off < 0) {
             throw new RuntimeException("off shouldn't be negative");
         }
-        Natives.setMemory(this.descriptor, off, buf);
+        if (DEFAULT_NAME.equals(name)) {
+            Natives.setMemory(instance.descriptor, off, buf);
+            return;
+        }
+        Natives.setMemoryNamed(instance.descriptor, name, off, buf);
+    }
+
+    public int pages() {
+        return Natives.getMemoryPages(instance.descriptor, name);
+    }
+
+    public int maxPages() {
+        return Natives.getMemoryMaxPages(instance.descriptor, name);
     }
 }
 
@@ -30,7 +56,10 @@ class InstanceImpl implements Instance {
 
 
     public Memory getMemory(String name) {
-        return mem;
+        if (name == null || name.equals(MemoryImpl.DEFAULT_NAME)) {
+            return mem;
+        }
+        return new MemoryImpl(this, name);
     }
 
 
@@ -38,8 +67,51 @@ class InstanceImpl implements Instance {
         return Natives.execute(descriptor, export, args);
     }
 
+    public TypedResults executeWithTypes(String export, long[] args) {
+        Object[] r = Natives.executeWithTypes(descriptor, export, args);
+        byte[] tags = (byte[]) r[1];
+        ValType[] types = new ValType[tags.length];
+        for (int i = 0; i < tags.length; i++) {
+            types[i] = ValType.of(tags[i]);
+        }
+        return new TypedResults((long[]) r[0], types);
+    }
+
+    public long[][] executeBatch(String export, long[][] args) {
+        return Natives.executeBatch(descriptor, export, args);
+    }
+
+    public long[] executeTable(String table, int index, long[] args) {
+        return Natives.executeTable(descriptor, table, index, args);
+    }
+
+    public byte[] executeAndPeek(String export, long[] args, int off, int len) {
+        if (off < 0 || len < 0) {
+            throw new RuntimeException("off or len shouldn't be negative");
+        }
+        return Natives.executeAndPeek(descriptor, export, args, off, len);
+    }
+
+    public void reset() {
+        Natives.reset(descriptor);
+    }
+
+    public List<ExportedGlobal> exportedGlobals() {
+        Object[] r = Natives.exportedGlobals(descriptor);
+        String[] names = (String[]) r[0];
+        byte[] types = (byte[]) r[1];
+        boolean[] mutable = (boolean[]) r[2];
+        List<ExportedGlobal> globals = new ArrayList<>(names.length);
+        for (int i = 0; i < names.length; i++) {
+            globals.add(new ExportedGlobal(names[i], ValType.of(types[i]), mutable[i]));
+        }
+        return globals;
+    }
+
     public void close() {
+        // throws if the instance is still executing in another thread
         Natives.close(descriptor);
+        descriptor = 0;
 
         Natives.MUTEX.lock();
 
@@ -1,7 +1,20 @@
 package com.archeros.wasmer;
 
 public interface Memory {
+    /**
+     * copy of memory at [off, off + len), not affected by later writes or grows
+     */
     byte[] read(int off, int len);
 
     void write(int off, byte[] buf);
+
+    /**
+     * current size in 64KiB pages
+     */
+    int pages();
+
+    /**
+     * maximum size in 64KiB pages, -1 if the memory has no maximum
+     */
+    int maxPages();
 }
@@ -22,7 +22,18 @@ public class Natives {
     /**
      * create instance and get the descriptor
      */
-    static native long createInstance(byte[] module, long options, int instanceId, String[] hostNames, byte[][] signatures);
+    static native long createInstance(byte[] module, long options, int instanceId, String[] hostNames, byte[][] signatures,
+                                      String[] globalNames, byte[] globalTypes, long[] globalValues);
+
+    /**
+     * check the module compiles under options without instantiation, return 0 if success
+     */
+    static native int validateModule(byte[] module, long options);
+
+    /**
+     * payload of the custom section called name, null if absent
+     */
+    static native byte[] customSection(byte[] module, String name);
 
 
     static long[] onHostFunction(int instanceId, int hostId, long[] args) {
@@ -30,20 +41,82 @@ public class Natives {
         return Natives.HOST_FUNCTIONS[instanceId][hostId].execute(ins, args);
     }
 
+    static byte[] onBytesHostFunction(int instanceId, int hostId, byte[] input) {
+        Instance ins = INSTANCES[instanceId];
+        return ((BytesHostFunction) Natives.HOST_FUNCTIONS[instanceId][hostId]).execute(ins, input);
+    }
+
     /**
      * execute function by function name
      */
     static native long[] execute(long descriptor, String function, long[] args);
 
+    /**
+     * execute function by function name, return [long[] results, byte[] result types]
+     */
+    static native Object[] executeWithTypes(long descriptor, String function, long[] args);
+
+    /**
+     * execute function by function name once per argument array, results are in the same order
+     */
+    static native long[][] executeBatch(long descriptor, String function, long[][] args);
+
+    /**
+     * execute function stored at index of the table exported as table
+     */
+    static native long[] executeTable(long descriptor, String table, int index, long[] args);
+
+    /**
+     * execute function by function name, then read memory at [off, off + length)
+     */
+    static native byte[] executeAndPeek(long descriptor, String function, long[] args, int off, int length);
+
 
     static native byte[] getMemory(long descriptor, int off, int length);
 
 
     static native void setMemory(long descriptor, int off, byte[] buf);
 
+    /**
+     * read memory exported as name
+     */
+    static native byte[] getMemoryNamed(long descriptor, String name, int off, int length);
+
+    /**
+     * write memory exported as name
+     */
+    static native void setMemoryNamed(long descriptor, String name, int off, byte[] buf);
+
+    /**
+     * current page count of memory exported as name
+     */
+    static native int getMemoryPages(long descriptor, String name);
+
+    /**
+     * maximum page count of memory exported as name, -1 if unbounded
+     */
+    static native int getMemoryMaxPages(long descriptor, String name);
+
+    /**
+     * restore exported memories and mutable globals to their values after instantiation
+     */
+    static native void reset(long descriptor);
+
+    /**
+     * exported globals as [String[] names, byte[] types, boolean[] mutable]
+     */
+    static native Object[] exportedGlobals(long descriptor);
+
+    /**
+     * function imports of module, return [String[] names, byte[][] param types, byte[][] return types]
+     */
+    static native Object[] requiredImports(byte[] module, long options);
+
     static native void close(long descriptor);
 
 
+    static final byte[] BYTES_SIGNATURE = new byte[]{5, 5};
+
     public static byte[] encodeSignature(List<ValType> params, List<ValType> r) {
         byte[] ret = new byte[1 + params.size()];
 
@@ -18,6 +18,10 @@ public class Options {
     private long moduleLinking;
     private long multiMemory;
     private long memory64;
+    private long sync;
+    private long wasi;
+    private long reset;
+    private long initialPages;
 
     private Options() {
     }
@@ -36,6 +40,10 @@ public class Options {
         return this;
     }
 
+    /**
+     * the flag is inverted: simd(false) sets the bit that enables the SIMD proposal, simd(true) and
+     * Options.empty() leave it disabled, the other proposal setters above and below behave the same
+     */
     public Options simd(boolean simd) {
         this.simd = simd ? 0 : (1L << 2);
         return this;
@@ -71,7 +79,46 @@ public class Options {
         return this;
     }
 
+    /**
+     * allow sharing the instance between threads, calls on it are serialized
+     */
+    public Options sync(boolean sync) {
+        this.sync = sync ? (1L << 32) : 0;
+        return this;
+    }
+
+    /**
+     * provide minimal wasi_snapshot_preview1 imports: fd_write to stdout/stderr, fd_fdstat_get, fd_seek and fd_close
+     * on stdio, empty args and environ, proc_exit
+     */
+    public Options wasi(boolean wasi) {
+        this.wasi = wasi ? (1L << 33) : 0;
+        return this;
+    }
+
+    /**
+     * keep a copy of exported memories and mutable globals after instantiation, required by Instance.reset,
+     * the copy costs as much as the memories hold
+     */
+    public Options reset(boolean reset) {
+        this.reset = reset ? (1L << 34) : 0;
+        return this;
+    }
+
+    /**
+     * grow the memory exported as "memory" to at least pages after instantiation, up to its maximum,
+     * a module exporting a single memory under another name has that memory grown instead,
+     * creating the instance fails if the module exports no memory, 0 keeps the size declared by module
+     */
+    public Options initialPages(int pages) {
+        if (pages < 0 || pages > 65536) {
+            throw new IllegalArgumentException("pages should be in [0, 65536]");
+        }
+        this.initialPages = ((long) pages) << 40;
+        return this;
+    }
+
     long bitmap() {
-        return threads | referenceTypes | simd | bulkMemory | multiValue | tailCall | moduleLinking | multiMemory | memory64;
+        return threads | referenceTypes | simd | bulkMemory | multiValue | tailCall | moduleLinking | multiMemory | memory64 | sync | wasi | reset | initialPages;
     }
 }
@@ -0,0 +1,18 @@
+package com.archeros.wasmer;
+
+import java.util.List;
+
+/**
+ * function imported by module, name is "module.field" as expected by HostFunction.getName
+ */
+public class RequiredImport {
+    public final String name;
+    public final List<ValType> params;
+    public final List<ValType> ret;
+
+    RequiredImport(String name, List<ValType> params, List<ValType> ret) {
+        this.name = name;
+        this.params = params;
+        this.ret = ret;
+    }
+}
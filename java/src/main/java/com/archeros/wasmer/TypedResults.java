@@ -0,0 +1,46 @@
+package com.archeros.wasmer;
+
+/**
+ * results of exported function with their value types,
+ * v128 takes two slots in values: low 64 bits, then high 64 bits,
+ * i64 values are raw bits, wasm doesn't distinguish signedness so values >= 2^63
+ * show up as negative longs, use Long.toUnsignedString when the function returns unsigned
+ */
+public class TypedResults {
+    public final long[] values;
+    public final ValType[] types;
+
+    TypedResults(long[] values, ValType[] types) {
+        this.values = values;
+        this.types = types;
+    }
+
+    /**
+     * results as Integer, Long, Float or Double, v128 as long[]{low, high}
+     */
+    public Object[] boxed() {
+        Object[] r = new Object[types.length];
+        int j = 0;
+        for (int i = 0; i < types.length; i++) {
+            long v = values[j++];
+            switch (types[i]) {
+                case I32:
+                    r[i] = (int) v;
+                    break;
+                case I64:
+                    r[i] = v;
+                    break;
+                case F32:
+                    r[i] = Float.intBitsToFloat((int) v);
+                    break;
+                case F64:
+                    r[i] = Double.longBitsToDouble(v);
+                    break;
+                case V128:
+                    r[i] = new long[]{v, values[j++]};
+                    break;
+            }
+        }
+        return r;
+    }
+}
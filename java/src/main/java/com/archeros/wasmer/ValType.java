@@ -4,7 +4,8 @@ public enum ValType {
     I32, // 0
     I64, // 1
     F32, // 2
-    F64;
+    F64, // 3
+    V128; // 4, passed as two longs: low 64 bits, then high 64 bits
 
     byte value() {
         switch (this) {
@@ -15,8 +16,26 @@ public enum ValType {
             case F32:
                 return 2;
             case F64:
+                return 3;
+            case V128:
                 return 4;
         }
         return 0;
     }
+
+    static ValType of(byte value) {
+        switch (value) {
+            case 0:
+                return I32;
+            case 1:
+                return I64;
+            case 2:
+                return F32;
+            case 3:
+                return F64;
+            case 4:
+                return V128;
+        }
+        throw new RuntimeException("unknown value type " + value);
+    }
 }
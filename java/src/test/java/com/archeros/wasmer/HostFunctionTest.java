@@ -0,0 +1,191 @@
+package com.archeros.wasmer;
+
+import org.junit.Test;
+
+import java.util.ArrayList;
+import java.util.Collections;
+import java.util.List;
+
+import static com.archeros.wasmer.HostFunction.EMPTY_LONGS;
+import static org.junit.Assert.*;
+
+public class HostFunctionTest {
+    static {
+        Natives.initialize(16);
+    }
+
+    static class Inc implements HostFunction {
+        final List<long[]> kept = new ArrayList<>();
+
+        @Override
+        public String getName() {
+            return "inc";
+        }
+
+        @Override
+        public long[] execute(Instance ins, long[] args) {
+            if (kept.size() < 16) {
+                kept.add(args);
+            }
+            return new long[]{args[0] + 1};
+        }
+
+        @Override
+        public List<ValType> getParams() {
+            return Collections.singletonList(ValType.I64);
+        }
+
+        @Override
+        public List<ValType> getRet() {
+            return Collections.singletonList(ValType.I64);
+        }
+    }
+
+    @Test
+    public void manyHostCalls() {
+        byte[] bin = TestUtil.readClassPathFile("testdata/host_loop.wasm");
+        Inc inc = new Inc();
+        int n = 100000;
+
+        try (Instance ins = Instance.create(bin, Options.empty(), Collections.singletonList(inc))) {
+            long[] r = ins.execute("run", new long[]{n});
+            assertArrayEquals(new long[]{n}, r);
+        }
+
+        // every call gets its own args array, so arrays kept by the handler are not overwritten
+        for (int i = 0; i < inc.kept.size(); i++) {
+            assertEquals(i, inc.kept.get(i)[0]);
+        }
+    }
+
+    static class Reenter implements HostFunction {
+        String error;
+
+        @Override
+        public String getName() {
+            return "reenter";
+        }
+
+        @Override
+        public long[] execute(Instance ins, long[] args) {
+            try {
+                ins.execute("two", EMPTY_LONGS);
+            } catch (RuntimeException e) {
+                error = e.getMessage();
+            }
+            return EMPTY_LONGS;
+        }
+
+        @Override
+        public List<ValType> getParams() {
+            return Collections.emptyList();
+        }
+
+        @Override
+        public List<ValType> getRet() {
+            return Collections.emptyList();
+        }
+    }
+
+    @Test
+    public void nestedExecuteIsRejected() {
+        byte[] bin = TestUtil.readClassPathFile("testdata/reenter.wasm");
+        Reenter reenter = new Reenter();
+
+        try (Instance ins = Instance.create(bin, Options.empty(), Collections.singletonList(reenter))) {
+            ins.execute("call_host", EMPTY_LONGS);
+            assertEquals("re-entrant execute from host function is not supported", reenter.error);
+
+            // the flag is cleared once the host function returns
+            assertArrayEquals(new long[]{2}, ins.execute("two", EMPTY_LONGS));
+        }
+    }
+
+    static class Reverse implements BytesHostFunction {
+        @Override
+        public String getName() {
+            return "reverse";
+        }
+
+        @Override
+        public byte[] execute(Instance ins, byte[] input) {
+            byte[] r = new byte[input.length];
+            for (int i = 0; i < input.length; i++) {
+                r[i] = input[input.length - 1 - i];
+            }
+            return r;
+        }
+    }
+
+    @Test
+    public void bytesHostFunction() {
+        byte[] bin = TestUtil.readClassPathFile("testdata/bytes.wasm");
+
+        try (Instance ins = Instance.create(bin, Options.empty(), Collections.singletonList(new Reverse()))) {
+            Memory mem = ins.getMemory(null);
+            assertArrayEquals(new long[]{5}, ins.execute("reverse", new long[]{8}));
+            assertArrayEquals("olleh".getBytes(), mem.read(16, 5));
+
+            // the full length is returned while only cap bytes are written
+            mem.write(16, new byte[8]);
+            assertArrayEquals(new long[]{5}, ins.execute("reverse", new long[]{3}));
+            assertArrayEquals(new byte[]{'o', 'l', 'l', 0, 0}, mem.read(16, 5));
+
+            for (int i = 0; i < 10000; i++) {
+                ins.execute("reverse", new long[]{8});
+            }
+            assertArrayEquals("olleh".getBytes(), mem.read(16, 5));
+        }
+
+        // the memory is checked when the instance is created rather than on the first call
+        try {
+            Instance.create(TestUtil.readClassPathFile("testdata/table.wasm"), Options.empty(), Collections.singletonList(new Reverse()));
+            fail("no memory export");
+        } catch (RuntimeException e) {
+            assertEquals("bytes host function requires the module to export its memory as memory", e.getMessage());
+        }
+    }
+
+    static class Swap implements HostFunction {
+        @Override
+        public String getName() {
+            return "swap";
+        }
+
+        @Override
+        public long[] execute(Instance ins, long[] args) {
+            return new long[]{args[1], args[0]};
+        }
+
+        @Override
+        public List<ValType> getParams() {
+            return Collections.singletonList(ValType.V128);
+        }
+
+        @Override
+        public List<ValType> getRet() {
+            return Collections.singletonList(ValType.V128);
+        }
+    }
+
+    @Test
+    public void v128RoundTrip() {
+        byte[] bin = TestUtil.readClassPathFile("testdata/simd.wasm");
+
+        // simd(false) is what enables the proposal
+        try (Instance ins = Instance.create(bin, Options.empty().simd(false), Collections.singletonList(new Swap()))) {
+            assertArrayEquals(new long[]{2, 1}, ins.execute("swap", new long[]{1, 2}));
+            assertArrayEquals(new long[]{-1, 5}, ins.execute("lanes", new long[]{-1, 5}));
+
+            TypedResults r = ins.executeWithTypes("swap", new long[]{1, 2});
+            assertArrayEquals(new ValType[]{ValType.V128}, r.types);
+            assertArrayEquals(new long[]{2, 1}, r.values);
+        }
+
+        try {
+            Instance.validate(bin, Options.empty());
+            fail("simd is disabled");
+        } catch (RuntimeException ignored) {
+        }
+    }
+}
@@ -0,0 +1,227 @@
+package com.archeros.wasmer;
+
+import org.junit.Test;
+
+import java.util.Arrays;
+import java.util.Collections;
+import java.util.List;
+
+import static org.junit.Assert.*;
+
+public class InstanceTest {
+    static {
+        Natives.initialize(16);
+    }
+
+    @Test
+    public void memoryPages() {
+        byte[] bin = TestUtil.readClassPathFile("testdata/memories.wasm");
+        try (Instance ins = Instance.create(bin, Options.empty(), null)) {
+            assertEquals(1, ins.getMemory("heap").pages());
+            assertEquals(-1, ins.getMemory("heap").maxPages());
+        }
+    }
+
+    @Test
+    public void memoryAfterClose() {
+        byte[] bin = TestUtil.readClassPathFile("testdata/memories.wasm");
+        Instance ins = Instance.create(bin, Options.empty(), null);
+        Memory mem = ins.getMemory(null);
+        Memory heap = ins.getMemory("heap");
+        assertArrayEquals("abc".getBytes(), heap.read(0, 3));
+        ins.close();
+
+        // memories obtained before close fail instead of reading freed memory
+        for (Memory m : new Memory[]{mem, heap}) {
+            try {
+                m.read(0, 3);
+                fail("read after close");
+            } catch (RuntimeException e) {
+                assertEquals("instance is closed", e.getMessage());
+            }
+        }
+    }
+
+    @Test
+    public void importedGlobals() {
+        byte[] bin = TestUtil.readClassPathFile("testdata/globals.wasm");
+        HostGlobal base = new HostGlobal("base", ValType.I64, -5);
+        HostGlobal f = new HostGlobal("m.f", ValType.F64, Double.doubleToLongBits(2.5));
+
+        try (Instance ins = Instance.create(bin, Options.empty(), null, Arrays.asList(base, f))) {
+            // read after create returned, the globals must outlive the native call that made them
+            assertArrayEquals(new long[]{-5}, ins.execute("base", Instance.EMPTY_LONGS));
+            assertEquals(2.5, Double.longBitsToDouble(ins.execute("f", Instance.EMPTY_LONGS)[0]), 0);
+        }
+    }
+
+    @Test
+    public void executeTable() {
+        byte[] bin = TestUtil.readClassPathFile("testdata/table.wasm");
+        try (Instance ins = Instance.create(bin, Options.empty(), null)) {
+            assertArrayEquals(new long[]{2}, ins.executeTable("t", 1, Instance.EMPTY_LONGS));
+            try {
+                ins.executeTable("t", 2, new long[]{1, 2});
+                fail("not exported");
+            } catch (RuntimeException e) {
+                assertEquals("table t slot 2 is not an exported function", e.getMessage());
+            }
+        }
+    }
+
+    static class Peek implements HostFunction {
+        byte[] seen;
+
+        @Override
+        public String getName() {
+            return "__peek";
+        }
+
+        @Override
+        public long[] execute(Instance ins, long[] args) {
+            seen = ins.getMemory("memory").read((int) args[0], (int) args[1]);
+            return Instance.EMPTY_LONGS;
+        }
+
+        @Override
+        public List<ValType> getParams() {
+            return Arrays.asList(ValType.I32, ValType.I32);
+        }
+
+        @Override
+        public List<ValType> getRet() {
+            return Collections.emptyList();
+        }
+    }
+
+    @Test
+    public void executeAndPeek() {
+        byte[] bin = TestUtil.readClassPathFile("testdata/peek.wasm");
+        Peek peek = new Peek();
+        long[] params = new long[]{Long.MAX_VALUE, Integer.toUnsignedLong(Integer.MAX_VALUE)};
+
+        for (Options options : new Options[]{Options.empty(), Options.empty().sync(true)}) {
+            try (Instance ins = Instance.create(bin, options, Collections.singletonList(peek))) {
+                byte[] r = ins.executeAndPeek("init", params, 0, 12);
+                assertArrayEquals(peek.seen, r);
+                assertArrayEquals(new byte[]{-1, -1, -1, -1, -1, -1, -1, 0x7f, -1, -1, -1, 0x7f}, r);
+
+                try {
+                    ins.executeAndPeek("init", params, 65530, 12);
+                    fail("out of bounds");
+                } catch (RuntimeException e) {
+                    assertEquals("memory access overflow", e.getMessage());
+                }
+            }
+        }
+    }
+
+    @Test
+    public void exportNames() {
+        byte[] bin = TestUtil.readClassPathFile("testdata/names.wasm");
+        try (Instance ins = Instance.create(bin, Options.empty(), null)) {
+            assertArrayEquals(new long[]{1}, ins.execute("\uD83D\uDE00", Instance.EMPTY_LONGS));
+            assertArrayEquals(new long[]{2}, ins.execute("\u00E9", Instance.EMPTY_LONGS));
+            assertArrayEquals(new long[]{3}, ins.execute("", Instance.EMPTY_LONGS));
+        }
+    }
+
+    @Test
+    public void largeMemoryRoundTrip() {
+        byte[] bin = TestUtil.readClassPathFile("testdata/memories.wasm");
+        try (Instance ins = Instance.create(bin, Options.empty().initialPages(64), null)) {
+            Memory mem = ins.getMemory(null);
+            byte[] buf = new byte[3 << 20];
+            for (int i = 0; i < buf.length; i++) {
+                buf[i] = (byte) (i * 31 + (i >> 16));
+            }
+
+            mem.write(1, buf);
+            assertArrayEquals(buf, mem.read(1, buf.length));
+            assertArrayEquals(buf, ins.getMemory("heap").read(1, buf.length));
+
+            try {
+                mem.write(mem.pages() * 65536 - buf.length + 1, buf);
+                fail("out of bounds");
+            } catch (RuntimeException e) {
+                assertEquals("memory access overflow", e.getMessage());
+            }
+        }
+    }
+
+    @Test
+    public void repeatedNoArgCalls() {
+        byte[] bin = TestUtil.readClassPathFile("testdata/names.wasm");
+        try (Instance ins = Instance.create(bin, Options.empty(), null)) {
+            for (int i = 0; i < 100000; i++) {
+                assertArrayEquals(new long[]{3}, ins.execute("", i % 2 == 0 ? Instance.EMPTY_LONGS : null));
+            }
+            try {
+                ins.execute("", new long[]{1});
+                fail("extra argument");
+            } catch (RuntimeException e) {
+                assertEquals("invalid params length", e.getMessage());
+            }
+        }
+    }
+
+    @Test
+    public void batchMatchesSingleCalls() {
+        byte[] bin = TestUtil.readClassPathFile("testdata/batch.wasm");
+        long[][] args = new long[][]{{6, 3}, {-9, 2}, {Long.MIN_VALUE, 1}, {7, -7}};
+
+        try (Instance ins = Instance.create(bin, Options.empty(), null)) {
+            long[][] batch = ins.executeBatch("div", args);
+            assertEquals(args.length, batch.length);
+            for (int i = 0; i < args.length; i++) {
+                assertArrayEquals(ins.execute("div", args[i]), batch[i]);
+            }
+
+            String single = null;
+            try {
+                ins.execute("div", new long[]{1, 0});
+                fail("divide by zero");
+            } catch (RuntimeException e) {
+                single = e.getMessage();
+            }
+
+            // the batch stops at the trap with the same error, calls before it have run
+            long before = ins.execute("calls", Instance.EMPTY_LONGS)[0];
+            try {
+                ins.executeBatch("div", new long[][]{{6, 3}, {1, 0}, {8, 2}});
+                fail("divide by zero");
+            } catch (RuntimeException e) {
+                assertEquals(single, e.getMessage());
+            }
+            assertArrayEquals(new long[]{before + 2}, ins.execute("calls", Instance.EMPTY_LONGS));
+        }
+    }
+
+    @Test
+    public void requiredImports() {
+        byte[] bin = TestUtil.readClassPathFile("testdata/peek.wasm");
+        List<RequiredImport> imports = Instance.requiredImports(bin, Options.empty());
+        assertEquals(1, imports.size());
+        assertEquals("env.__peek", imports.get(0).name);
+        assertEquals(Arrays.asList(ValType.I32, ValType.I32), imports.get(0).params);
+        assertEquals(Collections.emptyList(), imports.get(0).ret);
+
+        HostFunction wrong = new Peek() {
+            @Override
+            public List<ValType> getParams() {
+                return Arrays.asList(ValType.I64, ValType.I32);
+            }
+        };
+
+        // more failures than slots, each failed create gives its slot back
+        for (int i = 0; i < 32; i++) {
+            try {
+                Instance.create(bin, Options.empty(), Collections.singletonList(wrong));
+                fail("signature mismatch");
+            } catch (RuntimeException e) {
+                assertEquals("host function env.__peek signature mismatch, expect [I32, I32] -> []", e.getMessage());
+            }
+        }
+        Instance.create(bin, Options.empty(), Collections.singletonList(new Peek())).close();
+    }
+}
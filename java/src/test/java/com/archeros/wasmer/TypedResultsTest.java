@@ -0,0 +1,28 @@
+package com.archeros.wasmer;
+
+import org.junit.Test;
+
+import static org.junit.Assert.*;
+
+public class TypedResultsTest {
+    @Test
+    public void boxed() {
+        // values as they come from native: i32 and f32 zero extended, v128 in two slots
+        long[] values = new long[]{
+                Integer.toUnsignedLong(-7),
+                Integer.toUnsignedLong(Float.floatToIntBits(1.5f)),
+                Double.doubleToLongBits(-2.5),
+                0x0102030405060708L, -1L,
+                Long.MIN_VALUE,
+        };
+        ValType[] types = new ValType[]{ValType.I32, ValType.F32, ValType.F64, ValType.V128, ValType.I64};
+
+        Object[] r = new TypedResults(values, types).boxed();
+        assertEquals(types.length, r.length);
+        assertEquals(-7, r[0]);
+        assertEquals(1.5f, r[1]);
+        assertEquals(-2.5, r[2]);
+        assertArrayEquals(new long[]{0x0102030405060708L, -1L}, (long[]) r[3]);
+        assertEquals(Long.MIN_VALUE, r[4]);
+    }
+}
@@ -0,0 +1,7 @@
+;; div traps on a zero divisor, calls counts every call that reached it
+(module
+  (global $calls (mut i64) (i64.const 0))
+  (func (export "div") (param i64 i64) (result i64)
+    (global.set $calls (i64.add (global.get $calls) (i64.const 1)))
+    (i64.div_s (local.get 0) (local.get 1)))
+  (func (export "calls") (result i64) (global.get $calls)))
@@ -0,0 +1,7 @@
+;; passes "hello" to env.reverse and lets it write up to cap bytes at 16
+(module
+  (import "env" "reverse" (func $reverse (param i32 i32 i32 i32) (result i32)))
+  (memory (export "memory") 1)
+  (data (i32.const 0) "hello")
+  (func (export "reverse") (param $cap i32) (result i32)
+    (call $reverse (i32.const 0) (i32.const 5) (i32.const 16) (local.get $cap))))
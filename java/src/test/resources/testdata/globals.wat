@@ -0,0 +1,8 @@
+;; imported globals read back through functions, long after instantiation
+(module
+  (import "env" "base" (global $base i64))
+  (import "m" "f" (global $f f64))
+  (func (export "base") (result i64)
+    (global.get $base))
+  (func (export "f") (result f64)
+    (global.get $f)))
@@ -0,0 +1,13 @@
+;; calls env.inc n times, feeding each result back as the next argument
+(module
+  (import "env" "inc" (func $inc (param i64) (result i64)))
+  (func (export "run") (param $n i32) (result i64)
+    (local $i i32)
+    (local $acc i64)
+    (block $done
+      (loop $next
+        (br_if $done (i32.ge_u (local.get $i) (local.get $n)))
+        (local.set $acc (call $inc (local.get $acc)))
+        (local.set $i (i32.add (local.get $i) (i32.const 1)))
+        (br $next)))
+    (local.get $acc)))
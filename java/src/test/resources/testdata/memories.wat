@@ -0,0 +1,5 @@
+;; one memory exported as "memory" and "heap", so a named memory is there without the multi memory proposal
+(module
+  (memory $m (export "memory") 1)
+  (export "heap" (memory $m))
+  (data (i32.const 0) "abc"))
@@ -0,0 +1,5 @@
+;; exports whose names java encodes differently from plain utf-8: a supplementary character, a latin letter, and the empty name
+(module
+  (func (export "😀") (result i64) (i64.const 1))
+  (func (export "é") (result i64) (i64.const 2))
+  (func (export "") (result i64) (i64.const 3)))
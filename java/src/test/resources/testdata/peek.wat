@@ -0,0 +1,8 @@
+;; init stores its params at 0 and hands the region to env.__peek, like the example module
+(module
+  (import "env" "__peek" (func $peek (param i32 i32)))
+  (memory (export "memory") 1)
+  (func (export "init") (param $a i64) (param $b i32)
+    (i64.store (i32.const 0) (local.get $a))
+    (i32.store (i32.const 8) (local.get $b))
+    (call $peek (i32.const 0) (i32.const 12))))
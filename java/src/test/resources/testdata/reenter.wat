@@ -0,0 +1,7 @@
+;; "call_host" calls env.reenter, "two" is what the host tries to execute from inside it
+(module
+  (import "env" "reenter" (func $reenter))
+  (func (export "call_host")
+    (call $reenter))
+  (func (export "two") (result i32)
+    (i32.const 2)))
@@ -0,0 +1,7 @@
+;; swap passes a v128 through env.swap, lanes is computed in wasm
+(module
+  (import "env" "swap" (func $swap (param v128) (result v128)))
+  (func (export "swap") (param v128) (result v128)
+    (call $swap (local.get 0)))
+  (func (export "lanes") (param i64 i64) (result v128)
+    (i64x2.replace_lane 1 (i64x2.splat (local.get 0)) (local.get 1))))
@@ -0,0 +1,8 @@
+;; table filled by an elem segment, slot 1 is exported as "two", slot 2 is not exported
+(module
+  (table (export "t") 3 funcref)
+  (elem (i32.const 1) $two $add)
+  (func $two (export "two") (result i32)
+    (i32.const 2))
+  (func $add (param i32 i32) (result i32)
+    (i32.add (local.get 0) (local.get 1))))
@@ -1,3 +1,5 @@
+use crate::StringErr;
+
 static CHARS: [char; 16] = ['0', '1', '2', '3', '4', '5', '6', '7', '8', '9', 'a', 'b', 'c', 'd', 'e', 'f'];
 static CHARS_INV: [u8; 104] = [
     0, 0, 0, 0, 0, 0, 0, 0, // 0x08
@@ -25,23 +27,43 @@ pub fn to_hex(data: &[u8]) -> String {
     s
 }
 
-pub fn decode_hex(data: &str) -> Vec<u8> {
+pub fn decode_hex(data: &str) -> Result<Vec<u8>, StringErr> {
     let ascii = data.as_bytes();
-    if ascii.len() % 2 != 0 {
-        panic!("odd hex string {}\n", data);
+    if !ascii.len().is_multiple_of(2) {
+        return Err(StringErr(format!("odd hex string {}", data)));
     }
     let mut r: Vec<u8> = Vec::with_capacity(data.len() / 2);
     let mut j: u8 = 0;
-    let mut i: usize = 0;
-    for x in ascii {
-        if i % 2 != 0 {
-            j = j | CHARS_INV[*x as usize];
+    for (i, x) in ascii.iter().enumerate() {
+        let n = match CHARS_INV.get(*x as usize) {
+            Some(n) if *n != 0 || *x == b'0' => *n,
+            _ => return Err(StringErr(format!("invalid hex char at {}", i))),
+        };
+        if !i.is_multiple_of(2) {
+            j |= n;
             r.push(j);
             j = 0;
         } else {
-            j = CHARS_INV[*x as usize] << 4;
+            j = n << 4;
         }
-        i += 1;
     }
-    r
-}
\ No newline at end of file
+    Ok(r)
+}
+
+#[cfg(test)]
+mod test {
+    #[test]
+    fn decode_hex() {
+        assert_eq!(super::decode_hex("00ffA0").unwrap(), vec![0x00, 0xff, 0xa0]);
+        assert_eq!(super::decode_hex(&super::to_hex(b"abc")).unwrap(), b"abc");
+        assert!(super::decode_hex("0").is_err());
+        assert!(super::decode_hex("0g").is_err());
+
+        // arbitrary input must be rejected with an error rather than a panic
+        let mut seed = 0x2545f4914f6cdd1du64;
+        for n in 1..256 {
+            let bin = crate::utils::xorshift_bytes(&mut seed, n);
+            assert!(super::decode_hex(&String::from_utf8_lossy(&bin)).is_err());
+        }
+    }
+}
@@ -4,76 +4,319 @@ use jni::JNIEnv;
 // These objects are what you should use as arguments to your native function.
 // They carry extra lifetime information to prevent them escaping this context
 // and getting used after being GC'd.
-use jni::objects::{GlobalRef, JClass, JObject, JString, JValue, TypeArray};
+use jni::objects::{JObject, JValue};
 // This is just a pointer. We'll be returning it from our function.
 // We can't return one of the objects with lifetime information because the
 // lifetime checker won't let us.
-use jni::sys::{_jobject, jbyteArray, jint, jlong, jlongArray, jobject, jobjectArray, jstring};
-use wasmer::{
-    CompileError, ExportError, Exports, Features, Function, FunctionType, ImportObject, imports,
-    Instance, InstantiationError, Module, RuntimeError, Store, Type, Value,
-};
+use jni::sys::{jbyteArray, jint, jlong, jlongArray, jobjectArray, jstring};
+use wasmer::{Extern, Function, FunctionType, Global, ImportObject, Instance, LazyInit, Memory, RuntimeError, Type, Value, WasmerEnv};
+
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::ops::Deref;
+use std::sync::atomic::{AtomicBool, AtomicI64, AtomicUsize, Ordering};
 
 use crate::utils::JNIUtil;
-use crate::rp::Rp;
 use crate::{StringErr, ToVmType};
 
+// wasmer instance and the states shared with its host functions
+pub struct WasmInstance {
+    pub ins: Instance,
+    // imports of the instance, wasmer doesn't keep imported globals alive on its own
+    pub imports: ImportObject,
+    // set while a host function of this instance is running
+    pub in_host: Arc<AtomicBool>,
+    // number of jni calls in flight, close is rejected while it's not zero
+    users: AtomicUsize,
+    // exported memories and mutable globals right after instantiation, used by reset,
+    // only taken when the instance is created resettable since memories are copied
+    resettable: bool,
+    init_memories: Vec<(Memory, Vec<u8>)>,
+    init_globals: Vec<(Global, Value)>,
+    // present if the instance is shared between threads, jni calls are serialized by it
+    lock: Option<Mutex<()>>,
+    // thread holding the lock, calls from host functions on this thread don't lock again
+    owner: AtomicUsize,
+}
+
+// the registry hands instances to any jni thread, calls on them are serialized by lock when synchronized,
+// otherwise by java side which doesn't share the instance, only the saved extern refs of globals aren't Send
+unsafe impl Send for WasmInstance {}
+unsafe impl Sync for WasmInstance {}
+
+// an address unique to current thread
+fn thread_key() -> usize {
+    thread_local!(static KEY: u8 = const { 0 });
+    KEY.with(|k| k as *const u8 as usize)
+}
+
+impl WasmInstance {
+    pub fn new(ins: Instance, in_host: Arc<AtomicBool>, sync: bool, resettable: bool) -> Self {
+        let mut init_memories = Vec::new();
+        let mut init_globals = Vec::new();
+
+        if resettable {
+            for (_, e) in ins.exports.iter() {
+                match e {
+                    Extern::Memory(m) => init_memories.push((m.clone(), unsafe { m.data_unchecked().to_vec() })),
+                    Extern::Global(g) if g.ty().mutability.is_mutable() => init_globals.push((g.clone(), g.get())),
+                    _ => {}
+                }
+            }
+        }
+
+        WasmInstance {
+            ins,
+            imports: ImportObject::new(),
+            in_host,
+            users: AtomicUsize::new(0),
+            resettable,
+            init_memories,
+            init_globals,
+            lock: if sync { Some(Mutex::new(())) } else { None },
+            owner: AtomicUsize::new(0),
+        }
+    }
+
+    // restore exported memories and mutable globals, grown memory is zeroed but not shrunk
+    pub fn reset(&self) -> Result<(), StringErr> {
+        if !self.resettable {
+            return Err(StringErr("instance is not created with reset option".into()));
+        }
+        for (m, data) in self.init_memories.iter() {
+            let view = unsafe { m.data_unchecked_mut() };
+            view[..data.len()].copy_from_slice(data);
+            view[data.len()..].fill(0);
+        }
+        for (g, v) in self.init_globals.iter() {
+            g.set(v.clone())?;
+        }
+        Ok(())
+    }
+
+    // wasm of this instance can't be entered again while one of its host functions is running
+    pub fn check_execute(&self) -> Result<(), StringErr> {
+        if self.in_host.load(Ordering::SeqCst) {
+            return Err(StringErr("re-entrant execute from host function is not supported".into()));
+        }
+        Ok(())
+    }
+
+    // exported globals as (name, type, mutable), funcref and externref globals have no java value and are skipped
+    pub fn exported_globals(&self) -> Vec<(String, Type, bool)> {
+        self.ins.module().exports().globals()
+            .filter(|g| !matches!(g.ty().ty, Type::FuncRef | Type::ExternRef))
+            .map(|g| (g.name().to_string(), g.ty().ty, g.ty().mutability.is_mutable()))
+            .collect()
+    }
+}
+
+lazy_static::lazy_static! {
+    // live instances by descriptor, a closed or unknown descriptor is never dereferenced
+    static ref INSTANCES: Mutex<HashMap<jlong, Arc<WasmInstance>>> = Mutex::new(HashMap::new());
+}
+
+// descriptors are never reused, 0 is left for closed instances
+static NEXT_DESCRIPTOR: AtomicI64 = AtomicI64::new(1);
+
+fn instances() -> MutexGuard<'static, HashMap<jlong, Arc<WasmInstance>>> {
+    INSTANCES.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+// keep the instance alive until released, return its descriptor
+pub fn register(ins: WasmInstance) -> jlong {
+    let descriptor = NEXT_DESCRIPTOR.fetch_add(1, Ordering::SeqCst);
+    instances().insert(descriptor, Arc::new(ins));
+    descriptor
+}
+
+// keeps instance in use until dropped, holds the lock of synchronized instance
+pub struct InstanceGuard(Arc<WasmInstance>, Option<MutexGuard<'static, ()>>);
+
+impl InstanceGuard {
+    pub fn acquire(descriptor: jlong) -> Result<InstanceGuard, StringErr> {
+        let ins = {
+            // counted under the registry lock, so release can't remove it in between
+            let map = instances();
+            let ins = map.get(&descriptor).cloned().ok_or_else(|| StringErr("instance is closed".into()))?;
+            ins.users.fetch_add(1, Ordering::SeqCst);
+            ins
+        };
+        let mut guard = InstanceGuard(ins, None);
+
+        // the mutex lives as long as the arc held by guard, and drop releases it first
+        let w: &'static WasmInstance = unsafe { &*Arc::as_ptr(&guard.0) };
+        if let Some(lock) = w.lock.as_ref() {
+            if w.owner.load(Ordering::SeqCst) != thread_key() {
+                guard.1 = Some(lock.lock().unwrap_or_else(|e| e.into_inner()));
+                w.owner.store(thread_key(), Ordering::SeqCst);
+            }
+        }
+        Ok(guard)
+    }
+}
+
+impl Deref for InstanceGuard {
+    type Target = WasmInstance;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl Drop for InstanceGuard {
+    fn drop(&mut self) {
+        if self.1.is_some() {
+            self.0.owner.store(0, Ordering::SeqCst);
+            self.1 = None;
+        }
+        self.0.users.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+// find exported memory by name
+pub fn memory_by_name<'a>(ins: &'a Instance, name: &str) -> Result<&'a Memory, StringErr> {
+    ins.exports.get_memory(name)
+        .map_err(|_| StringErr(format!("memory export {} not found", name)))
+}
+
+// grow the memory exported as memory, or the only exported memory whatever its name,
+// to at least pages, clamped to its maximum
+pub fn warm_up_memory(ins: &Instance, pages: u32) -> Result<(), StringErr> {
+    let memories: Vec<&Memory> = ins.exports.iter().memories().map(|(_, m)| m).collect();
+    let mem = match memory_by_name(ins, "memory") {
+        Ok(m) => m,
+        Err(_) if memories.len() == 1 => memories[0],
+        Err(_) => return Err(StringErr("initial pages requires a memory exported as memory or a single exported memory".into())),
+    };
+    let target = mem.ty().maximum.map(|m| m.0.min(pages)).unwrap_or(pages);
+    if target > mem.size().0 {
+        mem.grow(target - mem.size().0)?;
+    }
+    Ok(())
+}
+
+// copies [off, off + len) into a new java array, so the result stays valid after the memory grows
 pub fn get_memory(
     env: JNIEnv,
     descriptor: jlong,
+    name: &str,
     off: jint,
     len: jint,
 ) -> Result<jbyteArray, StringErr> {
+    let ins = crate::get_ins_by_id(descriptor)?;
+    read_memory(env, memory_by_name(&ins.ins, name)?, off, len)
+}
+
+fn read_memory(env: JNIEnv, mem: &Memory, off: jint, len: jint) -> Result<jbyteArray, StringErr> {
     unsafe {
-        let ins = crate::get_ins_by_id(descriptor as usize);
-        let mem = ins.exports.get_memory("memory")?;
-        if (off + len) as u64 > mem.data_size() || off < 0 || len < 0 {
+        if off < 0 || len < 0 || off as u64 + len as u64 > mem.data_size() {
             return Err(StringErr("memory access overflow".into()));
         }
-        let slice = &mem.data_unchecked()[(off as usize)..(off + len) as usize];
+        let slice = &mem.data_unchecked()[(off as usize)..off as usize + len as usize];
         Ok(env.byte_array_from_slice(slice)?)
     }
 }
 
-pub fn set_memory(env: JNIEnv, descriptor: jlong, off: jint, buf: jbyteArray) -> Result<(), StringErr> {
+// current page count of memory
+pub fn memory_pages(descriptor: jlong, name: &str) -> Result<jint, StringErr> {
+    let ins = crate::get_ins_by_id(descriptor)?;
+    Ok(memory_by_name(&ins.ins, name)?.size().0 as jint)
+}
+
+// maximum page count of memory, -1 when the memory is unbounded
+pub fn memory_max_pages(descriptor: jlong, name: &str) -> Result<jint, StringErr> {
+    let ins = crate::get_ins_by_id(descriptor)?;
+    Ok(memory_by_name(&ins.ins, name)?.ty().maximum.map(|p| p.0 as jint).unwrap_or(-1))
+}
+
+pub fn set_memory(env: JNIEnv, descriptor: jlong, name: &str, off: jint, buf: jbyteArray) -> Result<(), StringErr> {
     unsafe {
-        let ins = crate::get_ins_by_id(descriptor as usize);
-        let bytes = env.convert_byte_array(buf)?;
-        let mem = ins.exports.get_memory("memory")?;
-        if (off as usize + bytes.len()) as usize > mem.data_unchecked().len() {
+        let ins = crate::get_ins_by_id(descriptor)?;
+        let len = env.get_array_length(buf)?;
+        let mem = memory_by_name(&ins.ins, name)?;
+        if off < 0 || off as u64 + len as u64 > mem.data_size() {
             return Err(StringErr("memory access overflow".into()));
         }
-        let mutable = mem.data_unchecked_mut();
-        mutable[off as usize..off as usize + bytes.len()].copy_from_slice(&bytes);
+        // copy from java array into memory directly, without an intermediate vec
+        let dst = &mut mem.data_unchecked_mut()[off as usize..off as usize + len as usize];
+        let dst = core::slice::from_raw_parts_mut(dst.as_mut_ptr() as *mut i8, dst.len());
+        env.get_byte_array_region(buf, 0, dst)?;
         Ok(())
     }
 }
 
-pub fn close(env: JNIEnv, descriptor: jlong) -> Result<(), StringErr> {
-    unsafe {
-        let mut ins: Rp<Instance> = (descriptor as usize).into();
+pub fn reset(_env: JNIEnv, descriptor: jlong) -> Result<(), StringErr> {
+    let ins = crate::get_ins_by_id(descriptor)?;
+    if ins.in_host.load(Ordering::SeqCst) {
+        return Err(StringErr("reset from host function is not supported".into()));
+    }
+    ins.reset()
+}
 
-        if ins.is_null() {
-            return Ok(());
-        }
-        ins.drop();
+// exported globals as [String[] names, byte[] type tags, boolean[] mutable]
+pub fn exported_globals(env: JNIEnv, descriptor: jlong) -> Result<jobjectArray, StringErr> {
+    let ins = crate::get_ins_by_id(descriptor)?;
+    let globals = ins.exported_globals();
+
+    let names = env.new_object_array(globals.len() as jint, "java/lang/String", JObject::null())?;
+    let mut tags = Vec::with_capacity(globals.len());
+    let mut mutable = Vec::with_capacity(globals.len());
+    for (i, (name, t, m)) in globals.iter().enumerate() {
+        let s = env.new_string(name)?;
+        env.set_object_array_element(names, i as jint, s)?;
+        env.delete_local_ref(s.into())?;
+        tags.push(type_to_u8!(t).ok_or_else(|| StringErr(format!("unsupported type of global {}", name)))?);
+        mutable.push(*m as u8);
+    }
+
+    let arr = env.new_object_array(3, "java/lang/Object", JObject::null())?;
+    env.set_object_array_element(arr, 0, names)?;
+    env.set_object_array_element(arr, 1, env.byte_array_from_slice(&tags)?)?;
+    let m = env.new_boolean_array(mutable.len() as jint)?;
+    env.set_boolean_array_region(m, 0, &mutable)?;
+    env.set_object_array_element(arr, 2, m)?;
+    Ok(arr)
+}
+
+pub fn close(_env: JNIEnv, descriptor: jlong) -> Result<(), StringErr> {
+    release(descriptor)
+}
+
+// free the instance, fails if it is still used by another thread
+pub fn release(descriptor: jlong) -> Result<(), StringErr> {
+    let mut map = instances();
+    let ins = match map.get(&descriptor) {
+        Some(ins) => ins,
+        None => return Ok(()),
+    };
+    if ins.users.load(Ordering::SeqCst) != 0 {
+        return Err(StringErr("instance is in use".into()));
     }
+    map.remove(&descriptor);
     Ok(())
 }
 
 
-pub fn create_host(store: &wasmer::Store, sig: (Vec<Type>, Vec<Type>), jvm: jni::JavaVM, ins: jint, host_id: jint) -> Function {
+pub fn create_host(store: &wasmer::Store, sig: (Vec<Type>, Vec<Type>), jvm: jni::JavaVM, ins: jint, host_id: jint, in_host: Arc<AtomicBool>) -> Function {
     let host_function_signature = FunctionType::new(sig.0.clone(), sig.1.clone());
+
     Function::new(store, &host_function_signature, move |_args| {
         let ret_types = sig.1.clone();
         let env: JNIEnv = as_rt!(jvm.get_env());
         let v = as_i64_vec!(_args, RuntimeError::new("unexpected param type"));
+        // handlers may keep args, so every call gets its own array
+        let args = as_rt!(env.slice_to_jlong_array(&v));
+        // execute is rejected while the flag is set, so host calls don't nest, restore the previous value anyway
+        let nested = in_host.swap(true, Ordering::SeqCst);
         let arr = env.call_static_method("com/archeros/wasmer/Natives", "onHostFunction", "(II[J)[J", &[
             JValue::Int(ins),
             JValue::Int(host_id),
-            JValue::Object(as_rt!(env.slice_to_jlong_array(&v)).into()),
+            JValue::Object(args.into()),
         ],
         );
+        in_host.store(nested, Ordering::SeqCst);
+        as_rt!(env.delete_local_ref(args.into()));
 
         let arr = as_rt!(arr);
         let o = match arr {
@@ -81,35 +324,546 @@ pub fn create_host(store: &wasmer::Store, sig: (Vec<Type>, Vec<Type>), jvm: jni:
             _ => return Err(RuntimeError::new("unexpected return type")),
         };
 
+        // host calls run inside the frame of execute, drop the refs of this call before the next one
         let v = env.jlong_array_to_vec(o.into_inner());
+        as_rt!(env.delete_local_ref(o));
         let v = as_rt!(v);
         ret_types.convert(v)
     })
 }
 
+// signature marker of host function taking and returning bytes
+pub const BYTES_SIGNATURE: [u8; 2] = [5, 5];
+
+// host function environment with access to the memory export of instance
+#[derive(WasmerEnv, Clone, Default)]
+pub struct MemoryEnv {
+    #[wasmer(export)]
+    memory: LazyInit<Memory>,
+}
+
+impl MemoryEnv {
+    #[allow(clippy::mut_from_ref)]
+    pub fn data(&self) -> Option<&mut [u8]> {
+        self.memory.get_ref().map(|m| unsafe { m.data_unchecked_mut() })
+    }
+}
+
+// host function of signature (ptr, len, out_ptr, out_cap) -> len, bytes at [ptr, ptr + len) is passed to java,
+// returned bytes are written at out_ptr up to out_cap, the full length is returned so guest could retry
+pub fn create_bytes_host(store: &wasmer::Store, jvm: jni::JavaVM, ins: jint, host_id: jint, in_host: Arc<AtomicBool>) -> Function {
+    let sig = FunctionType::new(vec![Type::I32; 4], vec![Type::I32]);
+
+    Function::new_with_env(store, &sig, MemoryEnv::default(), move |mem, args| {
+        let args = as_i64_vec!(args, RuntimeError::new("unexpected param type"));
+        let env: JNIEnv = as_rt!(jvm.get_env());
+        let data = mem.data().ok_or_else(|| RuntimeError::new("memory export memory not found"))?;
+        let input = as_rt!(read_bytes(data, args[0] as u32, args[1] as u32));
+        let input = as_rt!(env.byte_array_from_slice(input));
+
+        let nested = in_host.swap(true, Ordering::SeqCst);
+        let r = env.call_static_method("com/archeros/wasmer/Natives", "onBytesHostFunction", "(II[B)[B", &[
+            JValue::Int(ins),
+            JValue::Int(host_id),
+            JValue::Object(input.into()),
+        ],
+        );
+        in_host.store(nested, Ordering::SeqCst);
+
+        let o = match as_rt!(r) {
+            JValue::Object(o) => o,
+            _ => return Err(RuntimeError::new("unexpected return type")),
+        };
+        let output = if o.is_null() { Vec::new() } else { as_rt!(env.convert_byte_array(o.into_inner())) };
+        as_rt!(env.delete_local_ref(input.into()));
+        as_rt!(env.delete_local_ref(o));
+        // memory may be grown by java side, fetch it again
+        let data = mem.data().ok_or_else(|| RuntimeError::new("memory export memory not found"))?;
+        as_rt!(write_bytes(data, args[2] as u32, args[3] as u32, &output));
+        Ok(vec![Value::I32(output.len() as i32)])
+    })
+}
+
+pub fn read_bytes(data: &[u8], ptr: u32, len: u32) -> Result<&[u8], StringErr> {
+    data.get(ptr as usize..ptr as usize + len as usize)
+        .ok_or_else(|| StringErr("memory access overflow".into()))
+}
+
+// write at most cap bytes of src at ptr
+pub fn write_bytes(data: &mut [u8], ptr: u32, cap: u32, src: &[u8]) -> Result<(), StringErr> {
+    let n = src.len().min(cap as usize);
+    data.get_mut(ptr as usize..ptr as usize + n)
+        .ok_or_else(|| StringErr("memory access overflow".into()))?
+        .copy_from_slice(&src[..n]);
+    Ok(())
+}
+
+// look up exported function by java string name
+fn export_function(env: JNIEnv, ins: &WasmInstance, _method: jstring) -> Result<(Function, FunctionType), StringErr> {
+    ins.check_execute()?;
+
+    // java strings are modified utf-8, decode them instead of reading as plain utf-8
+    let method = String::from(env.get_string(_method.into())?);
+    let fun = ins.ins.exports.get_function(&method)?;
+    let sig = unsafe { fun.get_vm_function().signature.clone() };
+    Ok((fun.clone(), sig))
+}
+
+fn invoke(fun: &Function, sig: &FunctionType, a: Vec<i64>) -> Result<Vec<i64>, StringErr> {
+    if crate::utils::slots(sig.params()) != a.len() {
+        return Err(StringErr("invalid params length".into()));
+    }
+
+    let a = &sig.params().convert(a)?;
+    let results = fun.call(a)?;
+    Ok(as_i64_vec!(results, StringErr("unsupported return type".into())))
+}
+
+// function stored at index of the exported table, resolved to one that can be called from host
+pub fn table_function(ins: &Instance, table: &str, index: u32) -> Result<Function, StringErr> {
+    let t = ins.exports.get_table(table)
+        .map_err(|_| StringErr(format!("table export {} not found", table)))?;
+    let f = match t.get(index) {
+        Some(Value::FuncRef(Some(f))) => f,
+        Some(Value::FuncRef(None)) => return Err(StringErr(format!("table {} slot {} is null", table, index))),
+        Some(_) => return Err(StringErr(format!("table {} slot {} is not a function", table, index))),
+        None => return Err(StringErr(format!("table {} index {} out of bounds", table, index))),
+    };
+    callable(ins, f).ok_or_else(|| StringErr(format!("table {} slot {} is not an exported function", table, index)))
+}
+
+// table entries come without the call trampoline wasmer needs to enter wasm from host, calling them panics,
+// use the exported function sharing the code of the entry instead
+fn callable(ins: &Instance, f: Function) -> Option<Function> {
+    let entry = unsafe { f.get_vm_function() };
+    if entry.call_trampoline.is_some() {
+        return Some(f);
+    }
+    ins.exports.iter().functions()
+        .map(|(_, e)| e)
+        .find(|e| {
+            let e = unsafe { e.get_vm_function() };
+            e.call_trampoline.is_some() && e.address == entry.address
+        })
+        .cloned()
+}
+
+// call the function at index of the exported table, for host driven dynamic dispatch
+pub fn execute_table(
+    env: JNIEnv,
+    id: jlong,
+    table: &str,
+    index: jint,
+    args: jlongArray,
+) -> Result<jlongArray, StringErr> {
+    let ins = crate::get_ins_by_id(id)?;
+    ins.check_execute()?;
+    if index < 0 {
+        return Err(StringErr(format!("table {} index {} out of bounds", table, index)));
+    }
+    let fun = table_function(&ins.ins, table, index as u32)?;
+    let sig = fun.ty().clone();
+    let results = invoke(&fun, &sig, env.jlong_array_to_vec(args)?)?;
+    env.slice_to_jlong_array(&results)
+}
+
+// call exported function, return results and the function signature
+fn call_export(
+    env: JNIEnv,
+    ins: &WasmInstance,
+    _method: jstring,
+    args: jlongArray,
+) -> Result<(Vec<i64>, FunctionType), StringErr> {
+    let (fun, sig) = export_function(env, ins, _method)?;
+    let a: Vec<i64> = env.jlong_array_to_vec(args)?;
+    let results = invoke(&fun, &sig, a)?;
+    Ok((results, sig))
+}
+
+// every argument list of a batch should match the signature, checked before any call is made
+pub fn check_batch(params: &[Type], batch: &[Vec<i64>]) -> Result<(), StringErr> {
+    let n = crate::utils::slots(params);
+    match batch.iter().position(|a| a.len() != n) {
+        Some(i) => Err(StringErr(format!("invalid params length at batch index {}", i))),
+        None => Ok(()),
+    }
+}
+
+// call the same exported function once per argument list, in a single jni round trip
+pub fn execute_batch(
+    env: JNIEnv,
+    id: jlong,
+    _method: jstring,
+    batch: jobjectArray,
+) -> Result<jobjectArray, StringErr> {
+    let ins = crate::get_ins_by_id(id)?;
+    let (fun, sig) = export_function(env, &ins, _method)?;
+
+    let len = env.get_array_length(batch)?;
+    let mut args: Vec<Vec<i64>> = Vec::with_capacity(len as usize);
+    for i in 0..len {
+        let o = env.get_object_array_element(batch, i)?;
+        args.push(env.jlong_array_to_vec(o.into_inner())?);
+        env.delete_local_ref(o)?;
+    }
+    check_batch(sig.params(), &args)?;
+
+    let out = env.new_object_array(len, "[J", JObject::null())?;
+    for (i, a) in args.into_iter().enumerate() {
+        let results = env.slice_to_jlong_array(&invoke(&fun, &sig, a)?)?;
+        env.set_object_array_element(out, i as jint, results)?;
+        env.delete_local_ref(results.into())?;
+    }
+    Ok(out)
+}
+
 pub fn execute(
     env: JNIEnv,
     id: jlong,
     _method: jstring,
     args: jlongArray,
 ) -> Result<jlongArray, StringErr> {
-    unsafe {
-        let ins = crate::get_ins_by_id(id as usize);
+    let ins = crate::get_ins_by_id(id)?;
+    let (results, _) = call_export(env, &ins, _method, args)?;
+    env.slice_to_jlong_array(&results)
+}
+
+// type tag of each result, encoded as the host function signature does
+pub fn result_tags(sig: &FunctionType) -> Result<Vec<u8>, StringErr> {
+    let mut tags = Vec::with_capacity(sig.results().len());
+    for t in sig.results() {
+        match type_to_u8!(t) {
+            Some(x) => tags.push(x),
+            None => return Err(StringErr("unsupported return type".into())),
+        }
+    }
+    Ok(tags)
+}
+
+// execute function, return [long[] results, byte[] result type tags]
+pub fn execute_with_types(
+    env: JNIEnv,
+    id: jlong,
+    _method: jstring,
+    args: jlongArray,
+) -> Result<jobjectArray, StringErr> {
+    let ins = crate::get_ins_by_id(id)?;
+    let (results, sig) = call_export(env, &ins, _method, args)?;
+    let tags = result_tags(&sig)?;
+    let arr = env.new_object_array(2, "java/lang/Object", JObject::null())?;
+    env.set_object_array_element(arr, 0, env.slice_to_jlong_array(&results)?)?;
+    env.set_object_array_element(arr, 1, env.byte_array_from_slice(&tags)?)?;
+    Ok(arr)
+}
+
+// execute function then read the memory region [off, off + len) written by it
+pub fn execute_and_peek(
+    env: JNIEnv,
+    id: jlong,
+    _method: jstring,
+    args: jlongArray,
+    off: jint,
+    len: jint,
+) -> Result<jbyteArray, StringErr> {
+    // one guard for both steps, a sync instance can't run other calls in between
+    let ins = crate::get_ins_by_id(id)?;
+    call_export(env, &ins, _method, args)?;
+    read_memory(env, memory_by_name(&ins.ins, "memory")?, off, len)
+}
+
+#[cfg(test)]
+mod test {
+    use wasmer::{imports, Instance, Module, Value};
+
+    #[test]
+    fn memory_by_name() {
+        let store = crate::create_store(0);
+        let module = Module::new(&store, r#"(module (memory (export "mem") 1))"#).unwrap();
+        let ins = Instance::new(&module, &imports! {}).unwrap();
+
+        assert_eq!(super::memory_by_name(&ins, "mem").unwrap().size().0, 1);
+        assert_eq!(super::memory_by_name(&ins, "memory").unwrap_err().0, "memory export memory not found");
+    }
+
+    #[test]
+    fn memory_pages() {
+        let store = crate::create_store(0);
+        let module = Module::new(&store, r#"(module (memory (export "memory") 1 3))"#).unwrap();
+        let ins = Instance::new(&module, &imports! {}).unwrap();
+        let descriptor = super::register(super::WasmInstance::new(ins, Default::default(), false, false));
+
+        assert_eq!(super::memory_pages(descriptor, "memory").unwrap(), 1);
+        assert_eq!(super::memory_max_pages(descriptor, "memory").unwrap(), 3);
+        assert!(super::memory_max_pages(descriptor, "heap").is_err());
+
+        let ins = super::InstanceGuard::acquire(descriptor).unwrap();
+        super::memory_by_name(&ins.ins, "memory").unwrap().grow(1).unwrap();
+        drop(ins);
+        assert_eq!(super::memory_pages(descriptor, "memory").unwrap(), 2);
+        assert!(super::release(descriptor).is_ok());
+
+        let module = Module::new(&store, r#"(module (memory (export "memory") 1))"#).unwrap();
+        let ins = Instance::new(&module, &imports! {}).unwrap();
+        let descriptor = super::register(super::WasmInstance::new(ins, Default::default(), false, false));
+        assert_eq!(super::memory_max_pages(descriptor, "memory").unwrap(), -1);
+        assert!(super::release(descriptor).is_ok());
+    }
+
+    #[test]
+    fn warm_up_memory() {
+        let store = crate::create_store(0);
+        let module = Module::new(&store, r#"(module (memory (export "memory") 1 4))"#).unwrap();
+        let ins = Instance::new(&module, &imports! {}).unwrap();
+        let mem = super::memory_by_name(&ins, "memory").unwrap();
+
+        super::warm_up_memory(&ins, 3).unwrap();
+        assert_eq!(mem.size().0, 3);
+        super::warm_up_memory(&ins, 2).unwrap();
+        assert_eq!(mem.size().0, 3);
+        super::warm_up_memory(&ins, 8).unwrap();
+        assert_eq!(mem.size().0, 4);
+
+        let module = Module::new(&store, r#"(module (memory (export "heap") 1))"#).unwrap();
+        let ins = Instance::new(&module, &imports! {}).unwrap();
+        super::warm_up_memory(&ins, 2).unwrap();
+        assert_eq!(super::memory_by_name(&ins, "heap").unwrap().size().0, 2);
+
+        let module = Module::new(&store, r#"(module (memory 1))"#).unwrap();
+        let ins = Instance::new(&module, &imports! {}).unwrap();
+        assert_eq!(
+            super::warm_up_memory(&ins, 2).unwrap_err().0,
+            "initial pages requires a memory exported as memory or a single exported memory"
+        );
+    }
+
+    #[test]
+    fn table_function() {
+        let store = crate::create_store(0);
+        let module = Module::new(&store, r#"
+            (module
+                (table (export "table") 4 funcref)
+                (elem (i32.const 1) $two $three)
+                (func $two (export "two") (result i32) (i32.const 2))
+                (func $three (result i32) (i32.const 3))
+                (global (export "g") i32 (i32.const 0)))
+        "#).unwrap();
+        let ins = Instance::new(&module, &imports! {}).unwrap();
+
+        // placed by the elem segment, called like execute_table does
+        let f = super::table_function(&ins, "table", 1).unwrap();
+        assert_eq!(super::invoke(&f, f.ty(), vec![]).unwrap(), vec![2]);
+        assert_eq!(super::table_function(&ins, "table", 2).unwrap_err().0, "table table slot 2 is not an exported function");
+
+        let host = wasmer::Function::new_native(&store, || -> i32 { 4 });
+        ins.exports.get_table("table").unwrap().set(3, Value::FuncRef(Some(host))).unwrap();
+        assert_eq!(super::table_function(&ins, "table", 3).unwrap_err().0, "table table slot 3 is not an exported function");
+
+        assert_eq!(super::table_function(&ins, "table", 0).unwrap_err().0, "table table slot 0 is null");
+        assert_eq!(super::table_function(&ins, "table", 4).unwrap_err().0, "table table index 4 out of bounds");
+        assert_eq!(super::table_function(&ins, "g", 0).unwrap_err().0, "table export g not found");
+    }
+
+    #[test]
+    fn release_in_use() {
+        let store = crate::create_store(0);
+        let module = Module::new(&store, r#"(module (memory (export "memory") 1))"#).unwrap();
+        let ins = Instance::new(&module, &imports! {}).unwrap();
+        let descriptor = super::register(super::WasmInstance::new(ins, Default::default(), false, false));
+
+        let guard = super::InstanceGuard::acquire(descriptor).unwrap();
+        let closer = std::thread::spawn(move || super::release(descriptor));
+        assert_eq!(closer.join().unwrap().unwrap_err().0, "instance is in use");
+
+        drop(guard);
+        assert!(super::release(descriptor).is_ok());
+        assert!(super::InstanceGuard::acquire(0).is_err());
+        assert_eq!(super::InstanceGuard::acquire(descriptor).err().unwrap().0, "instance is closed");
+    }
+
+    #[test]
+    fn acquire_during_release() {
+        let store = crate::create_store(0);
+        let module = Module::new(&store, r#"(module (memory (export "memory") 1))"#).unwrap();
+
+        for _ in 0..20 {
+            let ins = Instance::new(&module, &imports! {}).unwrap();
+            let descriptor = super::register(super::WasmInstance::new(ins, Default::default(), false, false));
+
+            // bounded, so release isn't starved by users that never let go
+            let users: Vec<_> = (0..4).map(|_| std::thread::spawn(move || {
+                for _ in 0..1000 {
+                    match super::InstanceGuard::acquire(descriptor) {
+                        Ok(ins) => assert_eq!(ins.ins.exports.get_memory("memory").unwrap().size().0, 1),
+                        Err(e) => return assert_eq!(e.0, "instance is closed"),
+                    }
+                }
+            })).collect();
+
+            while let Err(e) = super::release(descriptor) {
+                assert_eq!(e.0, "instance is in use");
+                std::thread::yield_now();
+            }
+            users.into_iter().for_each(|t| t.join().unwrap());
+        }
+    }
+
+    #[test]
+    fn exported_globals() {
+        // reference typed globals need the proposal, enabled by default in a plain cranelift store
+        let store = wasmer::Store::new(&wasmer_engine_universal::Universal::new(wasmer_compiler_cranelift::Cranelift::default()).engine());
+        let module = Module::new(&store, r#"
+            (module
+                (global (export "counter") (mut i32) (i32.const 0))
+                (global (export "callback") funcref (ref.null func))
+                (global (export "pi") f64 (f64.const 3.14))
+                (global (export "handle") (mut externref) (ref.null extern)))
+        "#).unwrap();
+        let ins = Instance::new(&module, &imports! {}).unwrap();
+        let ins = super::WasmInstance::new(ins, Default::default(), false, false);
+
+        assert_eq!(ins.exported_globals(), vec![
+            ("counter".to_string(), wasmer::Type::I32, true),
+            ("pi".to_string(), wasmer::Type::F64, false),
+        ]);
+    }
+
+    #[test]
+    fn check_execute() {
+        let store = crate::create_store(0);
+        let module = Module::new(&store, r#"(module (memory (export "memory") 1))"#).unwrap();
+        let ins = Instance::new(&module, &imports! {}).unwrap();
+        let ins = super::WasmInstance::new(ins, Default::default(), false, false);
+
+        assert!(ins.check_execute().is_ok());
+        ins.in_host.store(true, std::sync::atomic::Ordering::SeqCst);
+        assert_eq!(ins.check_execute().unwrap_err().0, "re-entrant execute from host function is not supported");
+    }
+
+    #[test]
+    fn synchronized() {
+        let store = crate::create_store(0);
+        let module = Module::new(&store, r#"(module (memory (export "memory") 1))"#).unwrap();
+        let ins = Instance::new(&module, &imports! {}).unwrap();
+        let descriptor = super::register(super::WasmInstance::new(ins, Default::default(), true, false));
+
+        let threads: Vec<_> = (0..4).map(|_| std::thread::spawn(move || {
+            for _ in 0..1000 {
+                let _ins = super::InstanceGuard::acquire(descriptor).unwrap();
+                // nested acquire on the same thread, like a host function reading memory
+                let nested = super::InstanceGuard::acquire(descriptor).unwrap();
+                let mem = unsafe { nested.ins.exports.get_memory("memory").unwrap().data_unchecked_mut() };
+                let n = u32::from_le_bytes(mem[..4].try_into().unwrap());
+                mem[..4].copy_from_slice(&(n + 1).to_le_bytes());
+            }
+        })).collect();
+        threads.into_iter().for_each(|t| t.join().unwrap());
+
+        let ins = super::InstanceGuard::acquire(descriptor).unwrap();
+        let mem = unsafe { ins.ins.exports.get_memory("memory").unwrap().data_unchecked() };
+        assert_eq!(u32::from_le_bytes(mem[..4].try_into().unwrap()), 4000);
+        drop(ins);
+        assert!(super::release(descriptor).is_ok());
+    }
+
+    #[test]
+    fn bytes() {
+        let mut data = vec![0u8; 16];
+        data[..3].copy_from_slice(b"abc");
+        assert_eq!(super::read_bytes(&data, 0, 3).unwrap(), b"abc");
+        assert!(super::read_bytes(&data, 15, 2).is_err());
+
+        // output is truncated to capacity
+        super::write_bytes(&mut data, 8, 4, b"hello").unwrap();
+        assert_eq!(&data[8..13], b"hell\0");
+        assert!(super::write_bytes(&mut data, 14, 4, b"hello").is_err());
+    }
+
+    #[test]
+    fn reset() {
+        let store = crate::create_store(0);
+        let module = Module::new(&store, r#"
+            (module
+                (memory (export "memory") 1)
+                (data (i32.const 0) "abc")
+                (global (export "counter") (mut i64) (i64.const 7)))
+        "#).unwrap();
+        let ins = Instance::new(&module, &imports! {}).unwrap();
+        let plain = super::WasmInstance::new(ins.clone(), Default::default(), false, false);
+        assert_eq!(plain.reset().unwrap_err().0, "instance is not created with reset option");
 
-        let method = env.get_string(_method.into())?;
-        let s = method.to_str()?;
-        let fun = ins.exports.get_function(s)?;
-        let sig = fun.get_vm_function().signature.clone();
+        let ins = super::WasmInstance::new(ins, Default::default(), false, true);
+        let mem = ins.ins.exports.get_memory("memory").unwrap();
+        let counter = ins.ins.exports.get_global("counter").unwrap();
 
-        let a: Vec<i64> = env.jlong_array_to_vec(args)?;
+        unsafe { mem.data_unchecked_mut()[..4].copy_from_slice(b"xyzw") };
+        mem.grow(1).unwrap();
+        unsafe { mem.data_unchecked_mut()[70000] = 1 };
+        counter.set(Value::I64(100)).unwrap();
 
-        if sig.params().len() != a.len() {
-            return Err(StringErr("invalid params length".into()));
+        ins.reset().unwrap();
+        unsafe {
+            assert_eq!(&mem.data_unchecked()[..4], b"abc\0");
+            assert_eq!(mem.data_unchecked()[70000], 0);
         }
+        assert_eq!(counter.get(), Value::I64(7));
+    }
+
+    #[test]
+    fn reset_after_warm_up() {
+        let store = crate::create_store(0);
+        let module = Module::new(&store, r#"(module (memory (export "memory") 1) (data (i32.const 0) "abc"))"#).unwrap();
+        let ins = Instance::new(&module, &imports! {}).unwrap();
+        // same order as create_instance, the snapshot doesn't copy warmed pages
+        let ins = super::WasmInstance::new(ins, Default::default(), false, true);
+        super::warm_up_memory(&ins.ins, 4).unwrap();
+        assert_eq!(ins.init_memories[0].1.len(), 65536);
+
+        let mem = ins.ins.exports.get_memory("memory").unwrap();
+        unsafe { mem.data_unchecked_mut()[200000] = 1 };
+        ins.reset().unwrap();
+        assert_eq!(mem.size().0, 4);
+        unsafe {
+            assert_eq!(&mem.data_unchecked()[..3], b"abc");
+            assert_eq!(mem.data_unchecked()[200000], 0);
+        }
+    }
 
-        let a = &sig.params().convert(a)?;
-        let results = fun.call(&a)?;
-        let results = as_i64_vec!(results, StringErr("unsupported return type".into()));
-        return env.slice_to_jlong_array(&results);
+    #[test]
+    fn result_tags() {
+        let store = crate::create_store(0);
+        let module = Module::new(&store, r#"
+            (module
+                (func (export "pi") (result f64)
+                    f64.const 3.14))
+        "#).unwrap();
+        let f = module.exports().functions().next().unwrap();
+
+        assert_eq!(super::result_tags(f.ty()).unwrap(), vec![3]);
+
+        let multi = wasmer::FunctionType::new(vec![], vec![wasmer::Type::I32, wasmer::Type::F64]);
+        assert_eq!(super::result_tags(&multi).unwrap(), vec![0, 3]);
+
+        // u64::MAX is delivered as -1 but tagged i64, java side could read it as unsigned
+        let module = Module::new(&store, r#"
+            (module
+                (func (export "max") (result i64)
+                    i64.const 0xFFFFFFFFFFFFFFFF))
+        "#).unwrap();
+        let ins = Instance::new(&module, &imports! {}).unwrap();
+        let max = ins.exports.get_function("max").unwrap();
+        let sig = max.ty().clone();
+        assert_eq!(super::invoke(max, &sig, vec![]).unwrap(), vec![-1]);
+        assert_eq!(super::result_tags(&sig).unwrap(), vec![1]);
+    }
+
+    #[test]
+    fn check_batch() {
+        let params = [wasmer::Type::I32, wasmer::Type::V128];
+        assert!(super::check_batch(&params, &[vec![1, 2, 3], vec![4, 5, 6]]).is_ok());
+        assert!(super::check_batch(&params, &[]).is_ok());
+        assert_eq!(
+            super::check_batch(&params, &[vec![1, 2, 3], vec![4, 5]]).unwrap_err().0,
+            "invalid params length at batch index 1"
+        );
     }
 }
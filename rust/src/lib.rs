@@ -1,12 +1,10 @@
-#![feature(unchecked_math)] // allow unchecked math
-#![allow(warnings)]
-
 macro_rules! jni_ret {
     ($ex: expr, $env: ident, $default: expr) => {
-        match $ex {
+        // a panic unwinding out of an extern "system" function aborts the jvm, throw it as an exception instead
+        match std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| $ex)).unwrap_or_else(|p| Err(crate::panic_err(p))) {
             Ok(r) => r,
             Err(e) => {
-                $env.throw_new("java/lang/RuntimeException", e.0);
+                let _ = $env.throw_new("java/lang/RuntimeException", e.0);
                 $default
             }
         }
@@ -29,6 +27,11 @@ macro_rules! as_i64_vec {
                 Value::I64(x) => *x,
                 Value::F32(x) => x.to_bits() as u64 as i64,
                 Value::F64(x) => x.to_bits() as i64,
+                // v128 takes two slots, low lane first
+                Value::V128(x) => {
+                    v.push(*x as u64 as i64);
+                    (*x >> 64) as u64 as i64
+                }
                 _ => return Err($err),
             };
 
@@ -36,7 +39,7 @@ macro_rules! as_i64_vec {
         }
 
         v
-    };};
+    }};
 }
 
 macro_rules! u8_to_type {
@@ -46,6 +49,20 @@ macro_rules! u8_to_type {
             1 => Some(Type::I64),
             2 => Some(Type::F32),
             3 => Some(Type::F64),
+            4 => Some(Type::V128),
+            _ => None,
+        }
+    }};
+}
+
+macro_rules! type_to_u8 {
+    ($e: expr) => {{
+        match $e {
+            Type::I32 => Some(0u8),
+            Type::I64 => Some(1u8),
+            Type::F32 => Some(2u8),
+            Type::F64 => Some(3u8),
+            Type::V128 => Some(4u8),
             _ => None,
         }
     }};
@@ -62,33 +79,45 @@ macro_rules! decode_sig {
         {
             let mut r = Vec::new();
             for s in $sigs {
-                let ret = u8_to_type!(s[0]);
+                // the first byte is return type, 0xff for none
+                if s.is_empty() {
+                    return Err(StringErr("empty host function signature".into()));
+                }
+                // (ptr, len, out_ptr, out_cap) -> len, bytes are copied between memory and java
+                if s[..] == crate::instance::BYTES_SIGNATURE {
+                    r.push((vec![Type::I32; 4], vec![Type::I32]));
+                    continue;
+                }
+                let ret = match (s[0], u8_to_type!(s[0])) {
+                    (0xff, _) => None,
+                    (_, Some(t)) => Some(t),
+                    (x, None) => return Err(StringErr(format!("invalid host function return type {}", x))),
+                };
+                let mut params = Vec::with_capacity(s.len() - 1);
+                for x in s[1..].iter() {
+                    match u8_to_type!(*x) {
+                        Some(t) => params.push(t),
+                        None => return Err(StringErr(format!("invalid host function param type {}", x))),
+                    }
+                }
                 let pair: (Vec<Type>, Vec<Type>) = (
-                    // signature passed from java side is valid
-                    s[1..].iter().map(|x| u8_to_type!(*x).unwrap()).collect(),
+                    params,
                     ret.map(|f| vec![f]).unwrap_or(Vec::new()),
                 );
                 r.push(pair);
             }
             r
-        };
+        }
     };
 }
 
-#[macro_use]
-extern crate lazy_static;
-#[cfg(test)]
-#[macro_use]
-extern crate serde;
-#[macro_use]
-extern crate wasmer;
-
-use std::error::Error;
-use std::fmt::{Debug, Display, Formatter, Write};
+use std::collections::HashMap;
+use std::fmt::{Debug, Formatter};
 use std::ops::Deref;
 use std::ptr::null_mut;
 use std::str::Utf8Error;
-use std::sync::PoisonError;
+use std::sync::Arc;
+use std::sync::atomic::AtomicBool;
 
 // This is the interface to the JVM that we'll
 // call the majority of our methods on.
@@ -96,28 +125,30 @@ use jni::JNIEnv;
 // These objects are what you should use as arguments to your native function.
 // They carry extra lifetime information to prevent them escaping this context
 // and getting used after being GC'd.
-use jni::objects::{GlobalRef, JClass, JObject, JString, JValue, TypeArray};
+use jni::objects::{JClass, JObject};
 // This is just a pointer. We'll be returning it from our function.
 // We can't return one of the objects with lifetime information because the
 // lifetime checker won't let us.
-use jni::sys::{_jobject, jbyteArray, jint, jlong, jlongArray, jobject, jobjectArray, jstring};
+use jni::sys::{jbyteArray, jint, jlong, jlongArray, jobjectArray, jstring};
 use wasmer::{
-    CompileError, ExportError, Exports, Features, Function, FunctionType, ImportObject, imports,
-    Instance, InstantiationError, Module, RuntimeError, Store, Type, Value,
+    CompileError, ExportError, Exports, Features, FunctionType, Global, ImportObject,
+    Instance, InstantiationError, MemoryError, Module, RuntimeError, Store, Type, Value,
 };
-use wasmer::wasmparser::Operator;
 use wasmer_compiler_singlepass::Singlepass;
 use wasmer_engine_universal::Universal;
 use wasmer_compiler_cranelift::Cranelift;
 
 use utils::{JNIUtil, ToVmType};
 
-use crate::rp::Rp;
+// parameter and result types of a host function
+type Signature = (Vec<Type>, Vec<Type>);
 
+// general purpose helpers, not every function is used by the jni bindings
+#[allow(dead_code)]
 mod hex;
 mod utils;
-mod rp;
 mod instance;
+mod wasi;
 
 
 // This keeps rust from "mangling" the name and making it unique for this crate.
@@ -134,14 +165,69 @@ pub extern "system" fn Java_com_archeros_wasmer_Natives_createInstance(
     _ins: jint,
     _host_names: jobjectArray,
     _signatures: jobjectArray,
+    _global_names: jobjectArray,
+    _global_types: jbyteArray,
+    _global_values: jlongArray,
 ) -> jlong {
     jni_ret!(
-        create_instance(env, _class, _module, _options, _ins, _host_names, _signatures),
+        create_instance(env, _class, _module, _options, _ins, _host_names, _signatures, _global_names, _global_types, _global_values),
         env,
         0
     )
 }
 
+#[no_mangle]
+pub extern "system" fn Java_com_archeros_wasmer_Natives_validateModule(
+    env: JNIEnv,
+    // this is the class that owns our
+    // static method. Not going to be
+    // used, but still needs to have
+    // an argument slot
+    _class: JClass,
+    _module: jbyteArray,
+    _options: jlong,
+) -> jint {
+    jni_ret!(
+        env.convert_byte_array(_module)
+            .map_err(StringErr::from)
+            .and_then(|bytes| validate_module(&bytes, _options)),
+        env,
+        -1
+    )
+}
+
+#[no_mangle]
+pub extern "system" fn Java_com_archeros_wasmer_Natives_customSection(
+    env: JNIEnv,
+    // this is the class that owns our
+    // static method. Not going to be
+    // used, but still needs to have
+    // an argument slot
+    _class: JClass,
+    _module: jbyteArray,
+    name: jstring,
+) -> jbyteArray {
+    jni_ret!(
+        custom_section(env, _module, name),
+        env,
+        null_mut()
+    )
+}
+
+#[no_mangle]
+pub extern "system" fn Java_com_archeros_wasmer_Natives_requiredImports(
+    env: JNIEnv,
+    // this is the class that owns our
+    // static method. Not going to be
+    // used, but still needs to have
+    // an argument slot
+    _class: JClass,
+    _module: jbyteArray,
+    _options: jlong,
+) -> jobjectArray {
+    jni_ret!(required_imports_of(env, _module, _options), env, null_mut())
+}
+
 #[no_mangle]
 pub extern "system" fn Java_com_archeros_wasmer_Natives_execute(
     env: JNIEnv,
@@ -157,6 +243,101 @@ pub extern "system" fn Java_com_archeros_wasmer_Natives_execute(
     jni_ret!(crate::instance::execute(env, _id, _method, _args), env, null_mut())
 }
 
+#[no_mangle]
+pub extern "system" fn Java_com_archeros_wasmer_Natives_executeWithTypes(
+    env: JNIEnv,
+    // this is the class that owns our
+    // static method. Not going to be
+    // used, but still needs to have
+    // an argument slot
+    _class: JClass,
+    _id: jlong,
+    _method: jstring,
+    _args: jlongArray,
+) -> jobjectArray {
+    jni_ret!(crate::instance::execute_with_types(env, _id, _method, _args), env, null_mut())
+}
+
+#[no_mangle]
+pub extern "system" fn Java_com_archeros_wasmer_Natives_executeBatch(
+    env: JNIEnv,
+    // this is the class that owns our
+    // static method. Not going to be
+    // used, but still needs to have
+    // an argument slot
+    _class: JClass,
+    _id: jlong,
+    _method: jstring,
+    _batch: jobjectArray,
+) -> jobjectArray {
+    jni_ret!(crate::instance::execute_batch(env, _id, _method, _batch), env, null_mut())
+}
+
+#[no_mangle]
+pub extern "system" fn Java_com_archeros_wasmer_Natives_executeTable(
+    env: JNIEnv,
+    // this is the class that owns our
+    // static method. Not going to be
+    // used, but still needs to have
+    // an argument slot
+    _class: JClass,
+    _id: jlong,
+    table: jstring,
+    index: jint,
+    _args: jlongArray,
+) -> jlongArray {
+    jni_ret!(
+        env.get_string(table.into())
+            .map_err(StringErr::from)
+            .and_then(|s| crate::instance::execute_table(env, _id, &String::from(s), index, _args)),
+        env,
+        null_mut()
+    )
+}
+
+#[no_mangle]
+pub extern "system" fn Java_com_archeros_wasmer_Natives_executeAndPeek(
+    env: JNIEnv,
+    // this is the class that owns our
+    // static method. Not going to be
+    // used, but still needs to have
+    // an argument slot
+    _class: JClass,
+    _id: jlong,
+    _method: jstring,
+    _args: jlongArray,
+    off: jint,
+    len: jint,
+) -> jbyteArray {
+    jni_ret!(crate::instance::execute_and_peek(env, _id, _method, _args, off, len), env, null_mut())
+}
+
+#[no_mangle]
+pub extern "system" fn Java_com_archeros_wasmer_Natives_reset(
+    env: JNIEnv,
+    // this is the class that owns our
+    // static method. Not going to be
+    // used, but still needs to have
+    // an argument slot
+    _class: JClass,
+    _id: jlong,
+) {
+    jni_ret!(crate::instance::reset(env, _id), env, ())
+}
+
+#[no_mangle]
+pub extern "system" fn Java_com_archeros_wasmer_Natives_exportedGlobals(
+    env: JNIEnv,
+    // this is the class that owns our
+    // static method. Not going to be
+    // used, but still needs to have
+    // an argument slot
+    _class: JClass,
+    _id: jlong,
+) -> jobjectArray {
+    jni_ret!(crate::instance::exported_globals(env, _id), env, null_mut())
+}
+
 #[no_mangle]
 pub extern "system" fn Java_com_archeros_wasmer_Natives_close(
     env: JNIEnv,
@@ -182,7 +363,69 @@ pub extern "system" fn Java_com_archeros_wasmer_Natives_getMemory(
     off: jint,
     len: jint,
 ) -> jbyteArray {
-    jni_ret!(crate::instance::get_memory(env, _id, off, len), env, null_mut())
+    jni_ret!(crate::instance::get_memory(env, _id, "memory", off, len), env, null_mut())
+}
+
+#[no_mangle]
+pub extern "system" fn Java_com_archeros_wasmer_Natives_getMemoryPages(
+    env: JNIEnv,
+    // this is the class that owns our
+    // static method. Not going to be
+    // used, but still needs to have
+    // an argument slot
+    _class: JClass,
+    _id: jlong,
+    name: jstring,
+) -> jint {
+    jni_ret!(
+        env.get_string(name.into())
+            .map_err(StringErr::from)
+            .and_then(|s| crate::instance::memory_pages(_id, &String::from(s))),
+        env,
+        0
+    )
+}
+
+#[no_mangle]
+pub extern "system" fn Java_com_archeros_wasmer_Natives_getMemoryMaxPages(
+    env: JNIEnv,
+    // this is the class that owns our
+    // static method. Not going to be
+    // used, but still needs to have
+    // an argument slot
+    _class: JClass,
+    _id: jlong,
+    name: jstring,
+) -> jint {
+    jni_ret!(
+        env.get_string(name.into())
+            .map_err(StringErr::from)
+            .and_then(|s| crate::instance::memory_max_pages(_id, &String::from(s))),
+        env,
+        0
+    )
+}
+
+#[no_mangle]
+pub extern "system" fn Java_com_archeros_wasmer_Natives_getMemoryNamed(
+    env: JNIEnv,
+    // this is the class that owns our
+    // static method. Not going to be
+    // used, but still needs to have
+    // an argument slot
+    _class: JClass,
+    _id: jlong,
+    name: jstring,
+    off: jint,
+    len: jint,
+) -> jbyteArray {
+    jni_ret!(
+        env.get_string(name.into())
+            .map_err(StringErr::from)
+            .and_then(|s| crate::instance::get_memory(env, _id, &String::from(s), off, len)),
+        env,
+        null_mut()
+    )
 }
 
 #[no_mangle]
@@ -197,10 +440,33 @@ pub extern "system" fn Java_com_archeros_wasmer_Natives_setMemory(
     off: jint,
     buf: jbyteArray,
 ) {
-    jni_ret!(crate::instance::set_memory(env, _id, off, buf), env, ())
+    jni_ret!(crate::instance::set_memory(env, _id, "memory", off, buf), env, ())
+}
+
+#[no_mangle]
+pub extern "system" fn Java_com_archeros_wasmer_Natives_setMemoryNamed(
+    env: JNIEnv,
+    // this is the class that owns our
+    // static method. Not going to be
+    // used, but still needs to have
+    // an argument slot
+    _class: JClass,
+    _id: jlong,
+    name: jstring,
+    off: jint,
+    buf: jbyteArray,
+) {
+    jni_ret!(
+        env.get_string(name.into())
+            .map_err(StringErr::from)
+            .and_then(|s| crate::instance::set_memory(env, _id, &String::from(s), off, buf)),
+        env,
+        ()
+    )
 }
 
 
+#[allow(non_upper_case_globals)]
 mod features_enum {
     /// Threads proposal should be enabled
     pub const threads: u64 = 1;
@@ -222,12 +488,175 @@ mod features_enum {
     pub const memory64: u64 = 1 << 8;
 }
 
+#[allow(non_upper_case_globals)]
+mod options_enum {
+    /// Instance is shared between threads, calls on it are serialized
+    pub const sync: u64 = 1 << 32;
+    /// Register minimal wasi_snapshot_preview1 imports
+    pub const wasi: u64 = 1 << 33;
+    /// Keep a copy of exported memories and mutable globals after instantiation for reset
+    pub const reset: u64 = 1 << 34;
+    /// Bits 40..57 hold the page count the exported memory is grown to after instantiation
+    pub const initial_pages_shift: u64 = 40;
+    pub const initial_pages_mask: u64 = 0x1ffff;
+}
+
+fn create_store(_options: jlong) -> Store {
+    let mut features = Features::new();
+    let mask = _options as u64;
+
+    set_mask!(
+            mask,
+            features,
+            threads,
+            reference_types,
+            simd,
+            bulk_memory,
+            multi_value,
+            tail_call,
+            module_linking,
+            multi_memory,
+            memory64
+        );
+
+    Store::new(&Universal::new(Singlepass::default()).features(features).engine())
+}
+
+// check the module compiles under the enabled features, without instantiation
+fn validate_module(bytes: &[u8], _options: jlong) -> Result<jint, StringErr> {
+    let store = create_store(_options);
+    Module::validate(&store, bytes)?;
+    Ok(0)
+}
+
+// payload of custom section, null if module doesn't have it
+fn custom_section(env: JNIEnv, _module: jbyteArray, name: jstring) -> Result<jbyteArray, StringErr> {
+    let bytes = env.convert_byte_array(_module)?;
+    let name = String::from(env.get_string(name.into())?);
+    match utils::custom_section(&bytes, &name)? {
+        Some(data) => Ok(env.byte_array_from_slice(&data)?),
+        None => Ok(null_mut()),
+    }
+}
+
+// function imports of the module as [String[] "module.field" names, byte[][] param tags, byte[][] result tags]
+fn required_imports_of(env: JNIEnv, _module: jbyteArray, _options: jlong) -> Result<jobjectArray, StringErr> {
+    let store = create_store(_options);
+    let module = Module::new(&store, env.convert_byte_array(_module)?)?;
+    let imports = required_imports(&module);
+
+    let names = env.new_object_array(imports.len() as jint, "java/lang/String", JObject::null())?;
+    let params = env.new_object_array(imports.len() as jint, "[B", JObject::null())?;
+    let results = env.new_object_array(imports.len() as jint, "[B", JObject::null())?;
+    for (i, (m, name, f)) in imports.iter().enumerate() {
+        let full = format!("{}.{}", m, name);
+        let tags = |types: &[Type]| types.iter()
+            .map(|t| type_to_u8!(t).ok_or_else(|| StringErr(format!("unsupported type in import {}", full))))
+            .collect::<Result<Vec<u8>, StringErr>>();
+
+        let s = env.new_string(&full)?;
+        env.set_object_array_element(names, i as jint, s)?;
+        env.delete_local_ref(s.into())?;
+        let p = env.byte_array_from_slice(&tags(f.params())?)?;
+        env.set_object_array_element(params, i as jint, p)?;
+        env.delete_local_ref(p.into())?;
+        let r = env.byte_array_from_slice(&tags(f.results())?)?;
+        env.set_object_array_element(results, i as jint, r)?;
+        env.delete_local_ref(r.into())?;
+    }
+
+    let arr = env.new_object_array(3, "java/lang/Object", JObject::null())?;
+    env.set_object_array_element(arr, 0, names)?;
+    env.set_object_array_element(arr, 1, params)?;
+    env.set_object_array_element(arr, 2, results)?;
+    Ok(arr)
+}
+
+// function imports required by module, as (module, field, signature)
+fn required_imports(module: &Module) -> Vec<(String, String, FunctionType)> {
+    module.imports()
+        .filter_map(|i| match i.ty() {
+            wasmer::ExternType::Function(f) => Some((i.module().to_string(), i.name().to_string(), f.clone())),
+            _ => None,
+        })
+        .collect()
+}
+
+// host names and signatures are parallel arrays, signatures are indexed by host id
+fn check_hosts(names: &[String], sigs: &[Vec<u8>]) -> Result<(), StringErr> {
+    if names.len() != sigs.len() {
+        return Err(StringErr(format!(
+            "host names length {} mismatch signatures length {}",
+            names.len(),
+            sigs.len()
+        )));
+    }
+    Ok(())
+}
+
+// host names are "module.field", names without a module prefix are registered in env
+fn split_host_name(name: &str) -> (&str, &str) {
+    name.split_once('.').unwrap_or(("env", name))
+}
+
+// error for the first function import of module not provided by host functions or the wasi imports,
+// or provided with another signature, sigs are indexed like host names
+fn missing_import(module: &Module, host_names: &[String], sigs: &[Signature], wasi: bool) -> Option<String> {
+    let hosts: Vec<(&str, &str)> = host_names.iter().map(|n| split_host_name(n)).collect();
+    let wasi_exports = if wasi { Some(wasi::exports(module.store())) } else { None };
+    required_imports(module).into_iter()
+        .find_map(|(m, name, f)| {
+            if let (Some(w), true) = (&wasi_exports, m == wasi::NAMESPACE) {
+                return match w.get_function(&name) {
+                    Err(_) => Some(format!("unsupported wasi function {}.{}", m, name)),
+                    Ok(g) if g.ty() != &f => Some(format!("wasi function {}.{} signature mismatch, expect {}", m, name, g.ty())),
+                    Ok(_) => None,
+                };
+            }
+            match hosts.iter().position(|h| *h == (m.as_str(), name.as_str())) {
+                None => Some(format!("missing host function {}.{}", m, name)),
+                Some(i) if sigs[i].0 != f.params() || sigs[i].1 != f.results() => {
+                    Some(format!("host function {}.{} signature mismatch, expect {}", m, name, f))
+                }
+                _ => None,
+            }
+        })
+}
+
+// bytes host functions exchange data through the memory exported as memory
+fn check_bytes_memory(module: &Module) -> Result<(), StringErr> {
+    if module.exports().memories().any(|e| e.name() == "memory") {
+        return Ok(());
+    }
+    Err(StringErr("bytes host function requires the module to export its memory as memory".into()))
+}
+
+// globals imported by module, names are split like host names, mutability follows the import declared by module
+fn create_globals(store: &Store, module: &Module, names: Vec<String>, values: Vec<Value>) -> Result<Vec<(String, String, Global)>, StringErr> {
+    let mut r = Vec::with_capacity(names.len());
+    for (full, v) in names.iter().zip(values) {
+        let (m, name) = split_host_name(full);
+        let declared = module.imports().globals()
+            .find(|i| i.module() == m && i.name() == name)
+            .map(|i| *i.ty());
+        let g = match declared {
+            Some(t) if t.ty != v.ty() => {
+                return Err(StringErr(format!("global {} type mismatch, expect {:?}", full, t.ty)));
+            }
+            Some(t) if t.mutability.is_mutable() => Global::new_mut(store, v),
+            _ => Global::new(store, v),
+        };
+        r.push((m.to_string(), name.to_string(), g));
+    }
+    Ok(r)
+}
 
 #[inline]
-fn get_ins_by_id(id: usize) -> Rp<Instance> {
-    id.into()
+fn get_ins_by_id(id: jlong) -> Result<crate::instance::InstanceGuard, StringErr> {
+    crate::instance::InstanceGuard::acquire(id)
 }
 
+#[allow(clippy::too_many_arguments)]
 fn create_instance(
     env: JNIEnv,
     _class: JClass,
@@ -236,58 +665,304 @@ fn create_instance(
     ins: jint,
     _host_names: jobjectArray,
     _signatures: jobjectArray,
+    _global_names: jobjectArray,
+    _global_types: jbyteArray,
+    _global_values: jlongArray,
 ) -> Result<jlong, StringErr> {
-    unsafe {
-        let host_names = env.jstring_array_to_vec(_host_names)?;
-        let sigs = env.jbytes_array_to_vec(_signatures)?;
-        let sigs: Vec<(Vec<Type>, Vec<Type>)> = decode_sig!(sigs);
-        let mut features = Features::new();
-        let mask = _options as u64;
-
-        set_mask!(
-                mask,
-                features,
-                threads,
-                reference_types,
-                simd,
-                bulk_memory,
-                multi_value,
-                tail_call,
-                module_linking,
-                multi_memory,
-                memory64
-            );
-
-
-        // Create the store
-        let store = Store::new(&Universal::new(Singlepass::default()).features(features).engine());
-        let bytes = env.convert_byte_array(_module)?;
-        let module = Module::new(&store, bytes)?;
-
-        let mut import_object = ImportObject::new();
-        let mut namespace = Exports::new();
-
-        for i in 0..host_names.len() {
-            let name = host_names[i].clone();
-            let jvm = env.get_java_vm()?;
-            let s = sigs[i].clone();
-            let host_function = crate::instance::create_host(&store, s, jvm, ins, i as jint);
-            namespace.insert(name, host_function);
-        }
+    let global_names = env.jstring_array_to_vec(_global_names)?;
+    let global_types = if _global_types.is_null() { Vec::new() } else { env.convert_byte_array(_global_types)? };
+    if global_names.len() != global_types.len() {
+        return Err(StringErr("global names length mismatch global types length".into()));
+    }
+    let mut types = Vec::with_capacity(global_types.len());
+    for t in global_types {
+        types.push(u8_to_type!(t).ok_or_else(|| StringErr(format!("invalid global type {}", t)))?);
+    }
+    let global_values = types.convert(env.jlong_array_to_vec(_global_values)?)?;
+
+    let host_names = env.jstring_array_to_vec(_host_names)?;
+    let sigs = env.jbytes_array_to_vec(_signatures)?;
+    check_hosts(&host_names, &sigs)?;
+    let bytes_hosts: Vec<bool> = sigs.iter().map(|s| s[..] == crate::instance::BYTES_SIGNATURE).collect();
+    let sigs: Vec<Signature> = decode_sig!(sigs);
+
+    // Create the store
+    let store = create_store(_options);
+    let mask = _options as u64;
+    let bytes = env.convert_byte_array(_module)?;
+    let module = Module::new(&store, bytes)?;
 
-        import_object.register("env", namespace);
+    let wasi = mask & options_enum::wasi != 0;
+    if let Some(e) = missing_import(&module, &host_names, &sigs, wasi) {
+        return Err(StringErr(e));
+    }
+    if bytes_hosts.iter().any(|b| *b) {
+        check_bytes_memory(&module)?;
+    }
+
+    let in_host = Arc::new(AtomicBool::new(false));
+    let mut import_object = ImportObject::new();
+    let mut namespaces: HashMap<String, Exports> = HashMap::new();
 
-        let instance = Instance::new(&module, &import_object)?;
+    for i in 0..host_names.len() {
+        let (m, name) = split_host_name(&host_names[i]);
+        let jvm = env.get_java_vm()?;
+        let s = sigs[i].clone();
+        let host_function = if bytes_hosts[i] {
+            crate::instance::create_bytes_host(&store, jvm, ins, i as jint, in_host.clone())
+        } else {
+            crate::instance::create_host(&store, s, jvm, ins, i as jint, in_host.clone())
+        };
+        namespaces.entry(m.to_string()).or_default().insert(name, host_function);
+    }
 
-        let i = Rp::new(instance).ptr();
-        return Ok(i as jlong);
+    for (m, name, g) in create_globals(&store, &module, global_names, global_values)? {
+        namespaces.entry(m).or_default().insert(name, g);
     }
+
+    for (m, namespace) in namespaces {
+        import_object.register(m, namespace);
+    }
+    if wasi {
+        import_object.register(wasi::NAMESPACE, wasi::exports(&store));
+    }
+
+    let instance = Instance::new(&module, &import_object)?;
+    let sync = mask & options_enum::sync != 0;
+    let resettable = mask & options_enum::reset != 0;
+    // snapshot before warm up, so it holds the declared pages only, reset zeroes the warmed ones
+    let mut instance = crate::instance::WasmInstance::new(instance, in_host, sync, resettable);
+    instance.imports = import_object;
+
+    let initial_pages = (mask >> options_enum::initial_pages_shift) & options_enum::initial_pages_mask;
+    if initial_pages != 0 {
+        crate::instance::warm_up_memory(&instance.ins, initial_pages as u32)?;
+    }
+    Ok(crate::instance::register(instance))
 }
 
 #[cfg(test)]
 mod test {
+    use wasmer::Type;
+
+    use crate::{features_enum, Signature, StringErr};
+
     #[test]
     fn test() {}
+
+    #[test]
+    fn validate_disabled_proposal() {
+        let bin = wasmer::wat2wasm(br#"
+            (module
+                (func (export "splat") (param i32) (result v128)
+                    local.get 0
+                    i32x4.splat))
+        "#).unwrap();
+
+        assert!(super::validate_module(&bin, 0).is_err());
+        assert_eq!(super::validate_module(&bin, features_enum::simd as i64).unwrap(), 0);
+    }
+
+    fn decode(sigs: Vec<Vec<u8>>) -> Result<Vec<Signature>, StringErr> {
+        Ok(decode_sig!(sigs))
+    }
+
+    #[test]
+    fn decode_signatures() {
+        let sigs = decode(vec![vec![0xff, 0, 1], vec![3]]).unwrap();
+        assert_eq!(sigs[0], (vec![Type::I32, Type::I64], vec![]));
+        assert_eq!(sigs[1], (vec![], vec![Type::F64]));
+
+        assert!(decode(vec![vec![0xff], vec![]]).is_err());
+        assert!(decode(vec![vec![0xff, 9]]).is_err());
+        assert_eq!(decode(vec![vec![9]]).unwrap_err().0, "invalid host function return type 9");
+
+        let sigs = decode(vec![crate::instance::BYTES_SIGNATURE.to_vec()]).unwrap();
+        assert_eq!(sigs[0], (vec![Type::I32; 4], vec![Type::I32]));
+    }
+
+    #[test]
+    fn compile_error_offset() {
+        // a type section declaring one entry with an invalid form byte
+        let bin = [0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00, 0x01, 0x02, 0x01, 0x00];
+        let e = super::validate_module(&bin, 0).unwrap_err();
+        assert!(e.contains("offset 11"), "{}", e.0);
+    }
+
+    #[test]
+    fn fuzz_no_panic() {
+        // arbitrary input must be rejected with an error rather than a panic
+        let mut seed = 0x2545f4914f6cdd1du64;
+        for n in 0..256 {
+            let mut bin = vec![0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00];
+            bin.extend(crate::utils::xorshift_bytes(&mut seed, n));
+            if n > 0 {
+                assert!(super::validate_module(&bin, 0).is_err());
+            }
+            assert!(super::validate_module(&bin[8..], 0).is_err());
+            let _ = crate::utils::custom_section(&bin, "name");
+            let _ = crate::utils::custom_section(&bin[8..], "name");
+            let _ = decode(bin[8..].chunks(3).map(|c| c.to_vec()).collect());
+        }
+    }
+
+    #[test]
+    fn imported_globals() {
+        let store = super::create_store(0);
+        let module = wasmer::Module::new(&store, r#"
+            (module
+                (import "env" "base" (global $base i64))
+                (import "env" "counter" (global (mut i32)))
+                (import "math" "pi" (global $pi (mut f64)))
+                (global (export "copy") i64 (global.get $base))
+                (func (export "base") (result i64) (global.get $base))
+                (func (export "pi") (result f64) (global.get $pi)))
+        "#).unwrap();
+
+        let globals = super::create_globals(
+            &store,
+            &module,
+            vec!["base".into(), "counter".into(), "math.pi".into()],
+            vec![wasmer::Value::I64(-42), wasmer::Value::I32(1), wasmer::Value::F64(std::f64::consts::PI)],
+        ).unwrap();
+        assert!(globals[1].2.ty().mutability.is_mutable());
+        assert_eq!((globals[2].0.as_str(), globals[2].1.as_str()), ("math", "pi"));
+        // mutability is looked up in the module of the name, not env
+        assert!(globals[2].2.ty().mutability.is_mutable());
+
+        let mut import_object = wasmer::ImportObject::new();
+        let mut namespaces: std::collections::HashMap<String, wasmer::Exports> = Default::default();
+        for (m, name, g) in globals {
+            namespaces.entry(m).or_default().insert(name, g);
+        }
+        for (m, ns) in namespaces {
+            import_object.register(m, ns);
+        }
+        let ins = wasmer::Instance::new(&module, &import_object).unwrap();
+        let mut ins = crate::instance::WasmInstance::new(ins, Default::default(), false, false);
+        // moved into the instance like create_instance does, no local keeps the globals alive
+        ins.imports = import_object;
+        let ins = &ins.ins;
+        assert_eq!(ins.exports.get_global("copy").unwrap().get(), wasmer::Value::I64(-42));
+        assert_eq!(ins.exports.get_function("base").unwrap().call(&[]).unwrap()[0], wasmer::Value::I64(-42));
+        assert_eq!(ins.exports.get_function("pi").unwrap().call(&[]).unwrap()[0], wasmer::Value::F64(std::f64::consts::PI));
+
+        assert!(super::create_globals(&store, &module, vec!["base".into()], vec![wasmer::Value::I32(1)]).is_err());
+        assert_eq!(
+            super::create_globals(&store, &module, vec!["math.pi".into()], vec![wasmer::Value::I32(1)]).unwrap_err().0,
+            "global math.pi type mismatch, expect F64"
+        );
+    }
+
+    #[test]
+    fn panic_message() {
+        let e = std::panic::catch_unwind(|| panic!("boom {}", 1)).unwrap_err();
+        assert_eq!(super::panic_err(e).0, "panic: boom 1");
+        let e = std::panic::catch_unwind(|| panic!("boom")).unwrap_err();
+        assert_eq!(super::panic_err(e).0, "panic: boom");
+    }
+
+    #[test]
+    fn required_imports() {
+        let store = super::create_store(0);
+        let module = wasmer::Module::new(&store, r#"
+            (module
+                (import "env" "alert" (func (param i64)))
+                (import "env" "__peek" (func (param i32 i32) (result i32)))
+                (memory (export "memory") 1))
+        "#).unwrap();
+
+        let imports = super::required_imports(&module);
+        assert_eq!(imports.len(), 2);
+        assert_eq!(imports[0].1, "alert");
+        assert_eq!(imports[1].0, "env");
+        assert_eq!(imports[1].1, "__peek");
+        assert_eq!(imports[1].2.params(), &[wasmer::Type::I32, wasmer::Type::I32]);
+        assert_eq!(imports[1].2.results(), &[wasmer::Type::I32]);
+    }
+
+    #[test]
+    fn host_count_mismatch() {
+        let names = vec!["alert".to_string(), "__peek".to_string()];
+        let e = super::check_hosts(&names, &[vec![0xff, 1]]).unwrap_err();
+        assert_eq!(e.0, "host names length 2 mismatch signatures length 1");
+        assert!(super::check_hosts(&names, &[vec![0xff, 1], vec![0, 0, 0]]).is_ok());
+    }
+
+    #[test]
+    fn host_namespaces() {
+        assert_eq!(super::split_host_name("math.sin"), ("math", "sin"));
+        assert_eq!(super::split_host_name("io.fd.write"), ("io", "fd.write"));
+        assert_eq!(super::split_host_name("alert"), ("env", "alert"));
+
+        let store = super::create_store(0);
+        let module = wasmer::Module::new(&store, r#"
+            (module
+                (import "math" "sin" (func (param f64) (result f64)))
+                (import "io" "write" (func (param i32 i32)))
+                (import "env" "alert" (func (param i64))))
+        "#).unwrap();
+
+        let names = |v: &[&str]| v.iter().map(|s| s.to_string()).collect::<Vec<_>>();
+        let sigs: Vec<Signature> = vec![
+            (vec![Type::F64], vec![Type::F64]),
+            (vec![Type::I32, Type::I32], vec![]),
+            (vec![Type::I64], vec![]),
+        ];
+        assert_eq!(super::missing_import(&module, &names(&["math.sin", "io.write", "alert"]), &sigs, false), None);
+        assert_eq!(
+            super::missing_import(&module, &names(&["math.sin", "write", "alert"]), &sigs, false),
+            Some("missing host function io.write".to_string())
+        );
+
+        // same names, alert provided with an i32 param
+        let mut wrong = sigs.clone();
+        wrong[2].0 = vec![Type::I32];
+        assert_eq!(
+            super::missing_import(&module, &names(&["math.sin", "io.write", "alert"]), &wrong, false),
+            Some("host function env.alert signature mismatch, expect [I64] -> []".to_string())
+        );
+    }
+
+    #[test]
+    fn wasi_imports() {
+        let store = super::create_store(0);
+        let module = wasmer::Module::new(&store, r#"
+            (module
+                (import "wasi_snapshot_preview1" "fd_write" (func (param i32 i32 i32 i32) (result i32)))
+                (import "wasi_snapshot_preview1" "path_open"
+                    (func (param i32 i32 i32 i32 i32 i64 i64 i32 i32) (result i32))))
+        "#).unwrap();
+        assert_eq!(
+            super::missing_import(&module, &[], &[], true),
+            Some("unsupported wasi function wasi_snapshot_preview1.path_open".to_string())
+        );
+        assert_eq!(
+            super::missing_import(&module, &[], &[], false),
+            Some("missing host function wasi_snapshot_preview1.fd_write".to_string())
+        );
+
+        let module = wasmer::Module::new(&store, r#"
+            (module (import "wasi_snapshot_preview1" "proc_exit" (func (param i64))))
+        "#).unwrap();
+        assert_eq!(
+            super::missing_import(&module, &[], &[], true),
+            Some("wasi function wasi_snapshot_preview1.proc_exit signature mismatch, expect [I32] -> []".to_string())
+        );
+    }
+
+    #[test]
+    fn bytes_memory() {
+        let store = super::create_store(0);
+        let exported = wasmer::Module::new(&store, r#"(module (memory (export "memory") 1))"#).unwrap();
+        assert!(super::check_bytes_memory(&exported).is_ok());
+
+        let renamed = wasmer::Module::new(&store, r#"(module (memory (export "heap") 1))"#).unwrap();
+        assert_eq!(
+            super::check_bytes_memory(&renamed).unwrap_err().0,
+            "bytes host function requires the module to export its memory as memory"
+        );
+    }
 }
 
 macro_rules! impl_from {
@@ -300,26 +975,45 @@ macro_rules! impl_from {
     };
 }
 
-impl_from!(RuntimeError);
+// prefer Display for errors carrying more detail there, such as byte offsets
+macro_rules! impl_from_display {
+    ($display: ty) => {
+        impl From<$display> for StringErr {
+            fn from(e: $display) -> StringErr {
+                let s = format!("{}", e);
+                if s.is_empty() {
+                    return StringErr(format!("{:?}", e));
+                }
+                StringErr(s)
+            }
+        }
+    };
+}
+
+impl_from_display!(RuntimeError);
 impl_from!(jni::errors::Error);
 impl_from!(Utf8Error);
 impl_from!(ExportError);
-impl_from!(InstantiationError);
-impl_from!(CompileError);
+impl_from_display!(InstantiationError);
+impl_from_display!(CompileError);
+impl_from_display!(MemoryError);
+impl_from_display!(wasmer::wasmparser::BinaryReaderError);
 impl_from!(String);
 
 // Error handling utils
 pub struct StringErr(pub String);
 
-impl StringErr {
-    fn new<T: Deref<Target=str>>(t: T) -> Self {
-        StringErr(t.to_string())
+// message of a caught panic
+fn panic_err(p: Box<dyn std::any::Any + Send>) -> StringErr {
+    match p.downcast::<String>() {
+        Ok(s) => StringErr(format!("panic: {}", s)),
+        Err(p) => StringErr(format!("panic: {}", p.downcast_ref::<&str>().unwrap_or(&"unknown"))),
     }
 }
 
 impl Debug for StringErr {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
-        f.write_str(&self)
+        f.write_str(self)
     }
 }
 
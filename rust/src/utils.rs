@@ -1,29 +1,52 @@
-use std::{mem, ptr::null_mut};
-
 use jni::{JNIEnv, sys::{jlongArray, jobjectArray}};
-use lazy_static::__Deref;
 use wasmer::{RuntimeError, Type, Val, Value};
+use wasmer::wasmparser::{Parser, Payload};
 
-use crate::{jobject, StringErr};
+use crate::StringErr;
 
 pub trait ToVmType {
     fn convert(&self, src: Vec<i64>) -> Result<Vec<Val>, RuntimeError>;
 }
 
 
+// number of jlong slots taken by values of types, v128 is passed as two slots: low lane, then high lane
+pub fn slots(types: &[Type]) -> usize {
+    types.iter().map(|t| if *t == Type::V128 { 2 } else { 1 }).sum()
+}
+
 impl<T: core::ops::Deref<Target=[Type]>> ToVmType for T {
     fn convert(&self, src: Vec<i64>) -> Result<Vec<Val>, RuntimeError> {
-        let mut r: Vec<Val> = Vec::new();
-        for i in 0..src.len() {
+        if slots(self) != src.len() {
+            return Err(RuntimeError::new("unexpected values length"));
+        }
+
+        if self.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        // fast path for signatures with only i64 values, no conversion needed
+        if self.iter().all(|t| *t == Type::I64) {
+            return Ok(src.into_iter().map(Value::I64).collect());
+        }
+
+        let mut r: Vec<Val> = Vec::with_capacity(self.len());
+        let mut i = 0;
+        for t in self.iter() {
+            let x = src[i];
             let v =
-                match self[i] {
-                    Type::I32 => Value::I32(src[i] as u64 as u32 as i32),
-                    Type::F32 => Value::F32(f32::from_bits(src[i] as u64 as u32)),
-                    Type::I64 => Value::I64(src[i]),
-                    Type::F64 => Value::F64(f64::from_bits(src[i] as u64)),
+                match t {
+                    Type::I32 => Value::I32(x as u64 as u32 as i32),
+                    Type::F32 => Value::F32(f32::from_bits(x as u64 as u32)),
+                    Type::I64 => Value::I64(x),
+                    Type::F64 => Value::F64(f64::from_bits(x as u64)),
+                    Type::V128 => {
+                        i += 1;
+                        Value::V128((x as u64 as u128) | ((src[i] as u64 as u128) << 64))
+                    }
                     _ => return Err(RuntimeError::new("unexpected type"))
                 };
 
+            i += 1;
             r.push(v);
         }
 
@@ -31,12 +54,40 @@ impl<T: core::ops::Deref<Target=[Type]>> ToVmType for T {
     }
 }
 
+// payload of the first custom section called name, other sections are skipped without being decoded
+pub fn custom_section(bin: &[u8], name: &str) -> Result<Option<Vec<u8>>, StringErr> {
+    for payload in Parser::new(0).parse_all(bin) {
+        if let Payload::CustomSection { name: n, data, .. } = payload? {
+            if n == name {
+                return Ok(Some(data.to_vec()));
+            }
+        }
+    }
+    Ok(None)
+}
+
+// xorshift bytes for fuzz tests, the seed is carried between calls so runs are reproducible
+#[cfg(test)]
+pub fn xorshift_bytes(seed: &mut u64, n: usize) -> Vec<u8> {
+    let mut r = Vec::with_capacity(n);
+    for _ in 0..n {
+        *seed ^= *seed << 13;
+        *seed ^= *seed >> 7;
+        *seed ^= *seed << 17;
+        r.push(*seed as u8);
+    }
+    r
+}
 
 pub trait JNIUtil {
     fn jlong_array_to_vec(&self, arr: jlongArray) -> Result<Vec<i64>, StringErr>;
 
+    // allocates a new java array on every call
     fn slice_to_jlong_array(&self, arr: &[i64]) -> Result<jlongArray, StringErr>;
 
+    // fills a caller-owned array, its length must equal the slice length
+    fn slice_to_jlong_array_into(&self, slice: &[i64], arr: jlongArray) -> Result<(), StringErr>;
+
     fn jstring_array_to_vec(&self, arr: jobjectArray) -> Result<Vec<String>, StringErr>;
 
     fn jbytes_array_to_vec(&self, arr: jobjectArray) -> Result<Vec<Vec<u8>>, StringErr>;
@@ -49,18 +100,30 @@ impl JNIUtil for JNIEnv<'_> {
             return Ok(Vec::new());
         }
 
-        let arr = self.get_long_array_elements(arr, jni::objects::ReleaseMode::CopyBack)?;
-
-        let v: Vec<i64> = (0..arr.size()?).map(|i| unsafe { *arr.as_ptr().offset(i as isize) }).collect();
+        // copy the region out, zero-argument calls return before any copy
+        let len = self.get_array_length(arr)?;
+        let mut v = vec![0i64; len as usize];
+        if len > 0 {
+            self.get_long_array_region(arr, 0, &mut v)?;
+        }
         Ok(v)
     }
 
     fn slice_to_jlong_array(&self, slice: &[i64]) -> Result<jlongArray, StringErr> {
         let o = self.new_long_array(slice.len() as i32)?;
-        self.set_long_array_region(o, 0, slice)?;
+        self.slice_to_jlong_array_into(slice, o)?;
         Ok(o)
     }
 
+    fn slice_to_jlong_array_into(&self, slice: &[i64], arr: jlongArray) -> Result<(), StringErr> {
+        let len = self.get_array_length(arr)?;
+        if len as usize != slice.len() {
+            return Err(StringErr(format!("array length {} mismatch values length {}", len, slice.len())));
+        }
+        self.set_long_array_region(arr, 0, slice)?;
+        Ok(())
+    }
+
     fn jstring_array_to_vec(&self, arr: jobjectArray) -> Result<Vec<String>, StringErr> {
         if arr.is_null() {
             return Ok(Vec::new());
@@ -73,7 +136,7 @@ impl JNIUtil for JNIEnv<'_> {
         for i in 0..len {
             let o = self.get_object_array_element(arr, i)?;
             let s = self.get_string(o.into_inner().into())?;
-            v.push(s.to_str()?.into());
+            v.push(s.into());
         }
 
         Ok(v)
@@ -90,6 +153,9 @@ impl JNIUtil for JNIEnv<'_> {
 
         for i in 0..len {
             let o = self.get_object_array_element(arr, i)?;
+            if o.is_null() {
+                return Err(StringErr(format!("null byte array at index {}", i)));
+            }
             let bytes = self.convert_byte_array(o.into_inner())?;
             v.push(bytes);
         }
@@ -97,3 +163,62 @@ impl JNIUtil for JNIEnv<'_> {
         Ok(v)
     }
 }
+
+#[cfg(test)]
+mod test {
+    use wasmer::{Type, Value};
+
+    use super::ToVmType;
+
+    #[test]
+    fn convert_mixed() {
+        let sig = vec![Type::I32, Type::I64, Type::F32, Type::F64];
+        let src = vec![-1i64, -1i64, 1.5f32.to_bits() as i64, 2.5f64.to_bits() as i64];
+        let r = sig.convert(src).unwrap();
+        assert_eq!(r, vec![Value::I32(-1), Value::I64(-1), Value::F32(1.5), Value::F64(2.5)]);
+    }
+
+    #[test]
+    fn convert_i64_only() {
+        let sig = vec![Type::I64, Type::I64];
+        let r = sig.convert(vec![1, i64::MIN]).unwrap();
+        assert_eq!(r, vec![Value::I64(1), Value::I64(i64::MIN)]);
+        assert!(Vec::<Type>::new().convert(Vec::new()).unwrap().is_empty());
+        assert!(sig.convert(vec![1]).is_err());
+    }
+
+    #[test]
+    fn convert_v128() {
+        let sig = vec![Type::I32, Type::V128, Type::I64];
+        let r = sig.convert(vec![7, 0x0102030405060708, -1, 9]).unwrap();
+        assert_eq!(r, vec![
+            Value::I32(7),
+            Value::V128(0xffffffffffffffff_0102030405060708),
+            Value::I64(9),
+        ]);
+        assert!(sig.convert(vec![7, 1, 9]).is_err());
+    }
+
+    #[test]
+    fn custom_section() {
+        let mut bin = vec![0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00];
+        // type section with no entries, then custom section "meta" holding [1, 2, 3]
+        bin.extend_from_slice(&[0x01, 0x01, 0x00]);
+        bin.extend_from_slice(&[0x00, 0x08, 0x04, b'm', b'e', b't', b'a', 1, 2, 3]);
+        assert_eq!(super::custom_section(&bin, "meta").unwrap(), Some(vec![1, 2, 3]));
+        assert_eq!(super::custom_section(&bin, "name").unwrap(), None);
+
+        let named = wasmer::wat2wasm(br#"(module (func $answer))"#).unwrap();
+        let names = super::custom_section(&named, "name").unwrap().unwrap();
+        assert!(names.windows(6).any(|w| w == b"answer"));
+
+        bin.truncate(bin.len() - 1);
+        assert!(super::custom_section(&bin, "meta").is_err());
+        assert!(super::custom_section(b"abc", "meta").is_err());
+
+        // section size with a fifth leb128 byte overflowing u32
+        let mut overlong = bin[..8].to_vec();
+        overlong.extend_from_slice(&[0x00, 0x84, 0x80, 0x80, 0x80, 0x10, 0x00]);
+        assert!(super::custom_section(&overlong, "meta").is_err());
+    }
+}
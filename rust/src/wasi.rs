@@ -0,0 +1,230 @@
+use std::io::Write;
+
+use wasmer::{Exports, Function, RuntimeError, Store};
+
+use crate::instance::MemoryEnv;
+
+// minimal wasi_snapshot_preview1 imports: stdout/stderr output, stdio fd queries, empty args and environ,
+// proc_exit as trap
+pub const NAMESPACE: &str = "wasi_snapshot_preview1";
+
+const ERRNO_SUCCESS: i32 = 0;
+const ERRNO_BADF: i32 = 8;
+const ERRNO_FAULT: i32 = 21;
+const ERRNO_INVAL: i32 = 28;
+const ERRNO_IO: i32 = 29;
+const ERRNO_SPIPE: i32 = 70;
+
+const FILETYPE_CHARACTER_DEVICE: u8 = 2;
+const RIGHTS_FD_READ: u64 = 1 << 1;
+const RIGHTS_FD_WRITE: u64 = 1 << 6;
+
+pub fn exports(store: &Store) -> Exports {
+    let env = MemoryEnv::default();
+    let mut namespace = Exports::new();
+    namespace.insert("fd_write", Function::new_native_with_env(store, env.clone(), fd_write));
+    namespace.insert("fd_fdstat_get", Function::new_native_with_env(store, env.clone(), fd_fdstat_get));
+    namespace.insert("fd_seek", Function::new_native(store, fd_seek));
+    namespace.insert("fd_close", Function::new_native(store, fd_close));
+    namespace.insert("args_sizes_get", Function::new_native_with_env(store, env.clone(), sizes_get));
+    namespace.insert("args_get", Function::new_native(store, empty_get));
+    namespace.insert("environ_sizes_get", Function::new_native_with_env(store, env, sizes_get));
+    namespace.insert("environ_get", Function::new_native(store, empty_get));
+    namespace.insert("proc_exit", Function::new_native(store, proc_exit));
+    namespace
+}
+
+fn read_u32(data: &[u8], off: usize) -> Option<u32> {
+    let b = data.get(off..off.checked_add(4)?)?;
+    Some(u32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+}
+
+fn write_u32(data: &mut [u8], off: usize, v: u32) -> bool {
+    match off.checked_add(4).and_then(|end| data.get_mut(off..end)) {
+        Some(b) => {
+            b.copy_from_slice(&v.to_le_bytes());
+            true
+        }
+        None => false,
+    }
+}
+
+// write the iovec list at iovs to out, store the written bytes count at nwritten
+pub fn write_iovs(data: &mut [u8], iovs: u32, iovs_len: u32, nwritten: u32, out: &mut dyn Write) -> i32 {
+    let mut written: u32 = 0;
+
+    for i in 0..iovs_len as usize {
+        let at = iovs as usize + i * 8;
+        let (ptr, len) = match (read_u32(data, at), read_u32(data, at + 4)) {
+            (Some(p), Some(l)) => (p as usize, l as usize),
+            _ => return ERRNO_FAULT,
+        };
+        let buf = match data.get(ptr..ptr + len) {
+            Some(b) => b,
+            None => return ERRNO_FAULT,
+        };
+        if out.write_all(buf).is_err() {
+            return ERRNO_IO;
+        }
+        written = written.wrapping_add(len as u32);
+    }
+
+    if !write_u32(data, nwritten as usize, written) {
+        return ERRNO_FAULT;
+    }
+    ERRNO_SUCCESS
+}
+
+// test builds collect stdout of the calling thread here instead of printing it
+#[cfg(test)]
+thread_local! {
+    static STDOUT: std::cell::RefCell<Vec<u8>> = const { std::cell::RefCell::new(Vec::new()) };
+}
+
+fn write_stdout(data: &mut [u8], iovs: u32, iovs_len: u32, nwritten: u32) -> i32 {
+    #[cfg(test)]
+    return STDOUT.with(|out| write_iovs(data, iovs, iovs_len, nwritten, &mut *out.borrow_mut()));
+    #[cfg(not(test))]
+    write_iovs(data, iovs, iovs_len, nwritten, &mut std::io::stdout())
+}
+
+fn fd_write(env: &MemoryEnv, fd: i32, iovs: i32, iovs_len: i32, nwritten: i32) -> i32 {
+    let data = match env.data() {
+        Some(d) => d,
+        None => return ERRNO_INVAL,
+    };
+    match fd {
+        1 => write_stdout(data, iovs as u32, iovs_len as u32, nwritten as u32),
+        2 => write_iovs(data, iovs as u32, iovs_len as u32, nwritten as u32, &mut std::io::stderr()),
+        _ => ERRNO_BADF,
+    }
+}
+
+// fdstat of stdio at buf: character device, no flags, read rights for stdin and write rights for stdout/stderr
+pub fn fdstat(data: &mut [u8], fd: i32, buf: u32) -> i32 {
+    let rights = match fd {
+        0 => RIGHTS_FD_READ,
+        1 | 2 => RIGHTS_FD_WRITE,
+        _ => return ERRNO_BADF,
+    };
+    let mut stat = [0u8; 24];
+    stat[0] = FILETYPE_CHARACTER_DEVICE;
+    stat[8..16].copy_from_slice(&rights.to_le_bytes());
+    match data.get_mut(buf as usize..buf as usize + stat.len()) {
+        Some(b) => b.copy_from_slice(&stat),
+        None => return ERRNO_FAULT,
+    }
+    ERRNO_SUCCESS
+}
+
+fn fd_fdstat_get(env: &MemoryEnv, fd: i32, buf: i32) -> i32 {
+    match env.data() {
+        Some(data) => fdstat(data, fd, buf as u32),
+        None => ERRNO_INVAL,
+    }
+}
+
+// stdio is a stream, there is no offset to move
+fn fd_seek(fd: i32, _offset: i64, _whence: i32, _newoffset: i32) -> i32 {
+    match fd {
+        0..=2 => ERRNO_SPIPE,
+        _ => ERRNO_BADF,
+    }
+}
+
+// stdio stays open for the host, closing it is a no-op
+fn fd_close(fd: i32) -> i32 {
+    match fd {
+        0..=2 => ERRNO_SUCCESS,
+        _ => ERRNO_BADF,
+    }
+}
+
+// no args and environment variables are passed to guest
+fn sizes_get(env: &MemoryEnv, count: i32, buf_size: i32) -> i32 {
+    let data = match env.data() {
+        Some(d) => d,
+        None => return ERRNO_INVAL,
+    };
+    if !write_u32(data, count as u32 as usize, 0) || !write_u32(data, buf_size as u32 as usize, 0) {
+        return ERRNO_FAULT;
+    }
+    ERRNO_SUCCESS
+}
+
+fn empty_get(_ptrs: i32, _buf: i32) -> i32 {
+    ERRNO_SUCCESS
+}
+
+fn proc_exit(code: i32) -> Result<(), RuntimeError> {
+    Err(RuntimeError::new(format!("proc_exit({})", code)))
+}
+
+#[cfg(test)]
+mod test {
+    use wasmer::{ImportObject, Instance, Module};
+
+    #[test]
+    fn write_iovs() {
+        let mut data = vec![0u8; 64];
+        data[32..38].copy_from_slice(b"hello\n");
+        // two iovecs: "hel", "lo\n"
+        data[0..4].copy_from_slice(&32u32.to_le_bytes());
+        data[4..8].copy_from_slice(&3u32.to_le_bytes());
+        data[8..12].copy_from_slice(&35u32.to_le_bytes());
+        data[12..16].copy_from_slice(&3u32.to_le_bytes());
+
+        let mut out = Vec::new();
+        assert_eq!(super::write_iovs(&mut data, 0, 2, 16, &mut out), super::ERRNO_SUCCESS);
+        assert_eq!(out, b"hello\n");
+        assert_eq!(&data[16..20], &6u32.to_le_bytes());
+
+        assert_eq!(super::write_iovs(&mut data, 60, 1, 16, &mut out), super::ERRNO_FAULT);
+    }
+
+    #[test]
+    fn fdstat() {
+        let mut data = vec![0u8; 32];
+        assert_eq!(super::fdstat(&mut data, 1, 8), super::ERRNO_SUCCESS);
+        assert_eq!(data[8], super::FILETYPE_CHARACTER_DEVICE);
+        assert_eq!(&data[16..24], &super::RIGHTS_FD_WRITE.to_le_bytes());
+
+        assert_eq!(super::fdstat(&mut data, 3, 0), super::ERRNO_BADF);
+        assert_eq!(super::fdstat(&mut data, 0, 16), super::ERRNO_FAULT);
+    }
+
+    #[test]
+    fn hello_world() {
+        let store = crate::create_store(0);
+        // the calls a wasi-libc hello world makes on stdout: fdstat for isatty, write, then seek and close on exit
+        let module = Module::new(&store, r#"
+            (module
+                (import "wasi_snapshot_preview1" "fd_fdstat_get" (func $fdstat (param i32 i32) (result i32)))
+                (import "wasi_snapshot_preview1" "fd_write" (func $write (param i32 i32 i32 i32) (result i32)))
+                (import "wasi_snapshot_preview1" "fd_seek" (func $seek (param i32 i64 i32 i32) (result i32)))
+                (import "wasi_snapshot_preview1" "fd_close" (func $close (param i32) (result i32)))
+                (memory (export "memory") 1)
+                (data (i32.const 8) "\20\00\00\00\0c\00\00\00")
+                (data (i32.const 32) "hello world\n")
+                (func $expect (param i32 i32)
+                    (if (i32.ne (local.get 0) (local.get 1)) (then unreachable)))
+                (func (export "_start")
+                    (call $expect (call $fdstat (i32.const 1) (i32.const 64)) (i32.const 0))
+                    (call $expect (i32.load8_u (i32.const 64)) (i32.const 2))
+                    (call $expect (call $write (i32.const 1) (i32.const 8) (i32.const 1) (i32.const 16)) (i32.const 0))
+                    (call $expect (call $seek (i32.const 1) (i64.const 0) (i32.const 1) (i32.const 24)) (i32.const 70))
+                    (call $expect (call $close (i32.const 9)) (i32.const 8))
+                    (call $expect (call $close (i32.const 1)) (i32.const 0))))
+        "#).unwrap();
+        let mut imports = ImportObject::new();
+        imports.register(super::NAMESPACE, super::exports(&store));
+        let ins = Instance::new(&module, &imports).unwrap();
+
+        super::STDOUT.with(|out| out.borrow_mut().clear());
+        ins.exports.get_function("_start").unwrap().call(&[]).unwrap();
+        assert_eq!(super::STDOUT.with(|out| out.borrow().clone()), b"hello world\n");
+        let mem = ins.exports.get_memory("memory").unwrap();
+        // nwritten stored by fd_write
+        assert_eq!(unsafe { &mem.data_unchecked()[16..20] }, &12u32.to_le_bytes());
+    }
+}